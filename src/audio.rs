@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver};
+
+use macroquad::audio::{load_sound_from_bytes, play_sound, set_sound_volume, stop_sound, PlaySoundParams, Sound};
+
+use go_rs::FileError;
+
+// Sound effects are looked up here, next to music.ogg
+const SOUNDS_DIR: &str = "sounds";
+
+// Played when the playlist folder has no tracks
+const DEFAULT_TRACK: &str = "music.ogg";
+
+pub fn asset_error(e: macroquad::file::FileError) -> FileError {
+    let message = match e.kind {
+        macroquad::miniquad::fs::Error::IOError(e) => e.to_string(),
+        kind => kind.to_string()
+    };
+    FileError::Asset { path: e.path, message }
+}
+
+// WAV bytes and the length in seconds
+type Decoded = Result<(Vec<u8>, f32), FileError>;
+
+// Decoded with audrey, the same way macroquad would, since macroquad panics on files it cannot
+// read. The samples come back as 16-bit WAV, which macroquad reads back cheaply on the UI thread,
+// together with the length in seconds, which macroquad does not tell.
+fn decode_sound_file(path: &str) -> Decoded {
+    let bytes = std::fs::read(path).map_err(|source| FileError::Io { path: path.to_string(), source })?;
+    let error = |message: String| FileError::Asset { path: path.to_string(), message };
+
+    let mut reader = audrey::Reader::new(std::io::Cursor::new(bytes.as_slice())).map_err(|e| error(e.to_string()))?;
+    let description = reader.description();
+    let (channels, rate) = (description.channel_count(), description.sample_rate());
+    if !(1..=2).contains(&channels) {
+        return Err(error(format!("{} channels are not supported", channels)));
+    }
+    if rate == 0 {
+        return Err(error(String::from("sample rate is 0")));
+    }
+
+    let samples = reader.samples::<i16>().collect::<Result<Vec<_>, _>>().map_err(|e| error(e.to_string()))?;
+    let length = samples.len() as f32 / channels as f32 / rate as f32;
+    Ok((wav(&samples, channels as u16, rate), length))
+}
+
+// A 16-bit PCM WAV file of interleaved samples
+fn wav(samples: &[i16], channels: u16, rate: u32) -> Vec<u8> {
+    let data = (samples.len() * 2) as u32;
+    let mut bytes = Vec::with_capacity(44 + data as usize);
+    bytes.extend(b"RIFF");
+    bytes.extend((36 + data).to_le_bytes());
+    bytes.extend(b"WAVEfmt ");
+    bytes.extend(16u32.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.extend(channels.to_le_bytes());
+    bytes.extend(rate.to_le_bytes());
+    bytes.extend((rate * channels as u32 * 2).to_le_bytes());
+    bytes.extend((channels * 2).to_le_bytes());
+    bytes.extend(16u16.to_le_bytes());
+    bytes.extend(b"data");
+    bytes.extend(data.to_le_bytes());
+    for sample in samples {
+        bytes.extend(sample.to_le_bytes());
+    }
+    bytes
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Effect {
+    Place,
+    // Number of stones taken off the board
+    Capture(usize),
+    Illegal,
+    Pass,
+    Timeout
+}
+
+#[derive(Default)]
+pub struct Effects {
+    place: Option<Sound>,
+    capture: Option<Sound>,
+    illegal: Option<Sound>,
+    pass: Option<Sound>,
+    timeout: Option<Sound>
+}
+
+// A missing file leaves its effect silent; a file that is there but cannot be played is reported
+async fn load_effect(name: &str, errors: &mut Vec<FileError>) -> Option<Sound> {
+    let path = Path::new(SOUNDS_DIR).join(name);
+    if !path.exists() {
+        return None;
+    }
+    let sound = match decode_sound_file(path.to_string_lossy().as_ref()) {
+        Ok((bytes, _)) => load_sound_from_bytes(&bytes).await.map_err(asset_error),
+        Err(e) => Err(e)
+    };
+    match sound {
+        Ok(sound) => Some(sound),
+        Err(e) => {
+            errors.push(e);
+            None
+        }
+    }
+}
+
+impl Effects {
+    pub async fn load(errors: &mut Vec<FileError>) -> Self {
+        Effects {
+            place: load_effect("place.ogg", errors).await,
+            capture: load_effect("capture.ogg", errors).await,
+            illegal: load_effect("illegal.ogg", errors).await,
+            pass: load_effect("pass.ogg", errors).await,
+            timeout: load_effect("timeout.ogg", errors).await
+        }
+    }
+
+    pub fn play(&self, effect: Effect, volume: f32) {
+        let (sound, volume) = match effect {
+            Effect::Place => (self.place, volume),
+            // Bigger captures sound louder, up to full volume at ten stones
+            Effect::Capture(stones) => (self.capture.or(self.place), volume * (0.5 + 0.05 * stones.min(10) as f32)),
+            Effect::Illegal => (self.illegal, volume),
+            Effect::Pass => (self.pass, volume),
+            Effect::Timeout => (self.timeout, volume)
+        };
+        if let Some(sound) = sound {
+            play_sound(sound, PlaySoundParams { looped: false, volume });
+        }
+    }
+}
+
+// Tracks from the playlist folder. macroquad cannot tell when a sound ends, so the next track starts
+// once the current one has played for its decoded length.
+#[derive(Default)]
+pub struct Music {
+    tracks: Vec<String>,
+    // Tracks and their lengths, loaded the first time they play and kept, as macroquad cannot free sounds
+    loaded: HashMap<String, (Sound, f32)>,
+    current: Option<usize>,
+    // Seconds the current track has been playing
+    elapsed: f32,
+    // Track being decoded on another thread, so a long one does not stall the window
+    loading: Option<(usize, Receiver<Decoded>)>,
+    seed: u64
+}
+
+impl Music {
+    // .ogg and .wav files in the folder in name order, or music.ogg when there are none
+    pub fn new(dir: &str) -> Self {
+        let mut tracks = std::fs::read_dir(dir)
+            .map(|entries| entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ogg") || ext.eq_ignore_ascii_case("wav")))
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect::<Vec<_>>())
+            .unwrap_or_default();
+        tracks.sort();
+        if tracks.is_empty() {
+            tracks.push(String::from(DEFAULT_TRACK));
+        }
+
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0) | 1;
+        Music { tracks, loaded: HashMap::new(), current: None, elapsed: 0., loading: None, seed }
+    }
+
+    // Any other track when shuffling, otherwise the one after the current track
+    fn pick_next(&mut self, shuffle: bool) -> usize {
+        let count = self.tracks.len();
+        match self.current {
+            Some(current) if shuffle && count > 1 => {
+                // xorshift is plenty for picking songs
+                self.seed ^= self.seed << 13;
+                self.seed ^= self.seed >> 7;
+                self.seed ^= self.seed << 17;
+                (current + 1 + (self.seed % (count as u64 - 1)) as usize) % count
+            },
+            Some(current) => (current + 1) % count,
+            None if shuffle => (self.seed % count as u64) as usize,
+            None => 0
+        }
+    }
+
+    fn playing_sound(&self) -> Option<Sound> {
+        self.current.and_then(|c| self.loaded.get(&self.tracks[c])).map(|(sound, _)| *sound)
+    }
+
+    fn play(&mut self, index: usize, volume: f32) {
+        if let Some(playing) = self.playing_sound() {
+            stop_sound(playing);
+        }
+        self.current = Some(index);
+        self.elapsed = 0.;
+        if let Some(sound) = self.playing_sound() {
+            play_sound(sound, PlaySoundParams { looped: false, volume });
+        }
+    }
+
+    // Starts the next track, returning false if it first has to be decoded; poll starts it then
+    pub fn next(&mut self, shuffle: bool, volume: f32) -> bool {
+        if self.tracks.is_empty() {
+            return false;
+        }
+
+        let index = self.pick_next(shuffle);
+        let path = self.tracks[index].clone();
+        if self.loaded.contains_key(&path) {
+            self.loading = None;
+            self.play(index, volume);
+            return true;
+        }
+
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(decode_sound_file(path.as_str()));
+        });
+        self.loading = Some((index, receiver));
+        false
+    }
+
+    // Starts a track once it is decoded, returning Some(Ok) when it does. A track that cannot be
+    // decoded is dropped from the playlist and the current one keeps playing.
+    pub async fn poll(&mut self, volume: f32) -> Option<Result<(), FileError>> {
+        let (index, receiver) = self.loading.as_ref()?;
+        let index = *index;
+        let decoded = receiver.try_recv().ok()?;
+        self.loading = None;
+
+        let path = self.tracks[index].clone();
+        let track = match decoded {
+            Ok((bytes, length)) => load_sound_from_bytes(&bytes).await.map_err(asset_error).map(|sound| (sound, length)),
+            Err(e) => Err(e)
+        };
+        match track {
+            Ok(track) => {
+                self.loaded.insert(path, track);
+                self.play(index, volume);
+                Some(Ok(()))
+            },
+            Err(e) => {
+                self.tracks.remove(index);
+                if let Some(current) = self.current.as_mut().filter(|c| **c > index) {
+                    *current -= 1;
+                }
+                Some(Err(e))
+            }
+        }
+    }
+
+    // Counts the time played, returning true once the current track has ended
+    pub fn track_ended(&mut self, delta: f32) -> bool {
+        // The next one is already on its way
+        if self.loading.is_some() {
+            return false;
+        }
+        let Some(length) = self.current.and_then(|c| self.loaded.get(&self.tracks[c])).map(|(_, length)| *length) else {
+            return false;
+        };
+        self.elapsed += delta;
+        self.elapsed >= length
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        if let Some(sound) = self.playing_sound() {
+            set_sound_volume(sound, volume);
+        }
+    }
+
+    pub fn track_count(&self) -> usize {
+        self.tracks.len()
+    }
+
+    // File name of the playing track and its place in the playlist, counted from 1
+    pub fn playing(&self) -> Option<(String, usize)> {
+        let current = self.current?;
+        let name = Path::new(self.tracks[current].as_str())
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Some((name, current + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str, bytes: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("go_rs_{}_{}", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn undecodable_sounds_are_reported() {
+        let path = temp_file("garbage.ogg", b"not a sound at all");
+        assert!(matches!(decode_sound_file(path.as_str()), Err(FileError::Asset { .. })));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn sounds_are_decoded_to_wav() {
+        let samples = (0..1600).map(|i| (i * 20 - 16000) as i16).collect::<Vec<_>>();
+        let path = temp_file("stereo.wav", &wav(&samples, 2, 8000));
+        let (bytes, length) = decode_sound_file(path.as_str()).unwrap();
+        assert_eq!(bytes, wav(&samples, 2, 8000));
+        assert_eq!(length, 0.1);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn playlist_is_in_name_order() {
+        let dir = std::env::temp_dir().join(format!("go_rs_playlist_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["b.wav", "a.ogg", "notes.txt"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let mut music = Music::new(dir.to_string_lossy().as_ref());
+        assert_eq!(music.track_count(), 2);
+        assert_eq!(music.pick_next(false), 0);
+        music.current = Some(0);
+        assert_eq!(music.pick_next(false), 1);
+        assert_eq!(music.pick_next(true), 1);
+        assert!(!music.track_ended(1000.));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,548 @@
+//! Board positions and the rules for playing on them.
+
+use serde::{Serialize, Deserialize};
+
+use crate::scoring::ScoringMethod;
+
+/// Contents of a board point, also used as a player colour
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BoardCellOption{
+    /// A black stone
+    Black,
+    /// A white stone
+    White,
+    /// An empty point
+    None
+}
+
+impl BoardCellOption {
+    /// The other player's colour; None stays None
+    pub fn opposite(&self) -> Self {
+        match self {
+            BoardCellOption::Black => BoardCellOption::White,
+            BoardCellOption::White => BoardCellOption::Black,
+            BoardCellOption::None => BoardCellOption::None
+        }
+    }
+}
+
+/// Reason a move was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IllegalMove {
+    /// Only Black or White can move
+    NotAStone,
+    /// The other player is to move
+    NotYourTurn,
+    /// The point is off the board
+    OutOfBounds,
+    /// The point already has a stone
+    Occupied,
+    /// The stone would have no liberties and the ruleset forbids suicide
+    Suicide,
+    /// The move retakes a ko immediately
+    Ko,
+    /// The move repeats an earlier position under a superko rule
+    Superko,
+    /// The game is no longer in the playing phase
+    GameOver,
+    /// Black has not placed all free handicap stones yet
+    HandicapPending
+}
+
+impl std::fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IllegalMove::NotAStone => write!(f, "Only black or white stones can be played"),
+            IllegalMove::NotYourTurn => write!(f, "It is not your turn"),
+            IllegalMove::OutOfBounds => write!(f, "Point is outside the board"),
+            IllegalMove::Occupied => write!(f, "Point is already occupied"),
+            IllegalMove::Suicide => write!(f, "Suicide is not allowed under these rules"),
+            IllegalMove::Ko => write!(f, "Ko: the stone cannot be retaken immediately"),
+            IllegalMove::Superko => write!(f, "Superko: the move repeats an earlier position"),
+            IllegalMove::GameOver => write!(f, "The game is over"),
+            IllegalMove::HandicapPending => write!(f, "Black is still placing handicap stones")
+        }
+    }
+}
+
+/// Stones removed by a legal move
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MoveOutcome {
+    /// Opponent stones captured by the move
+    pub captured: Vec<[usize; 2]>,
+    /// Own stones removed by a suicide move, including the played stone
+    pub self_captured: Vec<[usize; 2]>
+}
+
+/// Rule set deciding suicide, ko, scoring and default komi
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Ruleset {
+    /// Japanese rules: no suicide, simple ko, territory scoring
+    #[default]
+    Japanese,
+    /// Korean rules, played like Japanese rules
+    Korean,
+    /// Chinese rules: positional superko, area scoring
+    Chinese,
+    /// American Go Association rules: situational superko, area scoring
+    Aga,
+    /// New Zealand rules: suicide allowed, situational superko, area scoring
+    NewZealand,
+    /// Tromp-Taylor rules: suicide allowed, positional superko, area scoring
+    TrompTaylor
+}
+
+impl Ruleset {
+    /// Whether a move that leaves its own group without liberties captures that group instead of being illegal
+    pub fn allows_suicide(&self) -> bool {
+        matches!(self, Ruleset::NewZealand | Ruleset::TrompTaylor)
+    }
+
+    /// Which repetitions are forbidden
+    pub fn ko_rule(&self) -> KoRule {
+        match self {
+            Ruleset::Japanese | Ruleset::Korean => KoRule::Simple,
+            Ruleset::Chinese | Ruleset::TrompTaylor => KoRule::PositionalSuperko,
+            Ruleset::Aga | Ruleset::NewZealand => KoRule::SituationalSuperko
+        }
+    }
+
+    /// How the final position is counted
+    pub fn scoring(&self) -> ScoringMethod {
+        match self {
+            Ruleset::Japanese | Ruleset::Korean => ScoringMethod::Territory,
+            _ => ScoringMethod::Area
+        }
+    }
+
+    /// Name as written in SGF RU properties
+    pub fn name(&self) -> &'static str {
+        match self {
+            Ruleset::Japanese => "Japanese",
+            Ruleset::Korean => "Korean",
+            Ruleset::Chinese => "Chinese",
+            Ruleset::Aga => "AGA",
+            Ruleset::NewZealand => "NZ",
+            Ruleset::TrompTaylor => "Tromp-Taylor"
+        }
+    }
+
+    /// Parses a ruleset name, ignoring case, spaces, dashes and underscores
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().replace(['-', '_', ' '], "").as_str() {
+            "japanese" | "jp" => Some(Ruleset::Japanese),
+            "korean" | "kr" => Some(Ruleset::Korean),
+            "chinese" | "cn" => Some(Ruleset::Chinese),
+            "aga" => Some(Ruleset::Aga),
+            "nz" | "newzealand" => Some(Ruleset::NewZealand),
+            "tromptaylor" | "tt" => Some(Ruleset::TrompTaylor),
+            _ => None
+        }
+    }
+
+    /// Komi usually given to White in an even game
+    pub fn default_komi(&self) -> f32 {
+        match self {
+            Ruleset::Japanese | Ruleset::Korean => 6.5,
+            Ruleset::NewZealand => 7.0,
+            _ => 7.5
+        }
+    }
+}
+
+/// Which repeated positions are illegal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KoRule {
+    /// Only immediately retaking a single stone ko is forbidden
+    Simple,
+    /// No board position may ever repeat
+    PositionalSuperko,
+    /// No position may repeat with the same player to move
+    SituationalSuperko
+}
+
+/// Mixes a 64-bit value, used for Zobrist keys and the random move generator
+pub(crate) fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+fn zobrist_key(x: usize, y: usize, color: BoardCellOption) -> u64 {
+    let c = match color {
+        BoardCellOption::Black => 0,
+        BoardCellOption::White => 1,
+        BoardCellOption::None => return 0
+    };
+    splitmix64(((y * 64 + x) * 2 + c) as u64)
+}
+
+fn default_to_move() -> BoardCellOption {
+    BoardCellOption::Black
+}
+
+/// A position together with the rules needed to play legal moves on it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GoBoard{
+    /// Number of lines in each direction
+    pub size: usize,
+    /// Points indexed as `board[y][x]`, y counting down from the top
+    pub board: Vec<Vec<BoardCellOption>>,
+    /// Prisoners taken by Black
+    pub captured_black: usize,
+    /// Prisoners taken by White
+    pub captured_white: usize,
+    /// Player whose turn it is
+    #[serde(default = "default_to_move")]
+    pub to_move: BoardCellOption,
+    /// Rules used for suicide and ko
+    #[serde(default)]
+    pub ruleset: Ruleset,
+    /// Zobrist hashes of every position reached so far, with the player to move in it
+    #[serde(default)]
+    pub history: Vec<(u64, BoardCellOption)>
+}
+
+impl GoBoard {
+    /// An empty board with Black to move
+    pub fn new(size: usize, ruleset: Ruleset) -> Self {
+        let mut board = GoBoard { 
+            size, 
+            board: vec![vec![BoardCellOption::None; size]; size],
+            captured_black: 0,
+            captured_white: 0,
+            to_move: BoardCellOption::Black,
+            ruleset,
+            history: vec![]
+        };
+        board.reset_history();
+        board
+    }
+
+    /// Plays a stone for the player to move, capturing and checking suicide and ko
+    ///
+    /// On error the position is left unchanged
+    pub fn play(&mut self, color: BoardCellOption, x: usize, y: usize) -> Result<MoveOutcome, IllegalMove> {
+        if color == BoardCellOption::None {
+            return Err(IllegalMove::NotAStone);
+        }
+        if color != self.to_move {
+            return Err(IllegalMove::NotYourTurn);
+        }
+        if x >= self.size || y >= self.size {
+            return Err(IllegalMove::OutOfBounds);
+        }
+        if self.board[y][x] != BoardCellOption::None {
+            return Err(IllegalMove::Occupied);
+        }
+
+        let previous = (self.board.clone(), self.captured_black, self.captured_white);
+
+        self.board[y][x] = color;
+        let captured = self.capture_neighbours(x, y);
+
+        let own = Cluster::from(self, x, y);
+        let mut self_captured = vec![];
+        if !own.has_liberties(self) {
+            // Nothing was captured, otherwise the group would have a liberty, so undoing the placement is enough
+            if !self.ruleset.allows_suicide() {
+                self.board[y][x] = BoardCellOption::None;
+                return Err(IllegalMove::Suicide);
+            }
+            self.clear_cluster(&own);
+            self_captured = own.pieces;
+        }
+
+        let position = (self.hash(), color.opposite());
+        if let Err(e) = self.check_ko(position) {
+            (self.board, self.captured_black, self.captured_white) = previous;
+            return Err(e);
+        }
+
+        self.to_move = color.opposite();
+        self.history.push(position);
+
+        Ok(MoveOutcome { captured, self_captured })
+    }
+
+    /// Passes the turn to the other player
+    pub fn pass(&mut self, color: BoardCellOption) -> Result<(), IllegalMove> {
+        if color != self.to_move {
+            return Err(IllegalMove::NotYourTurn);
+        }
+
+        self.to_move = color.opposite();
+        self.history.push((self.hash(), self.to_move));
+        Ok(())
+    }
+
+    fn check_ko(&self, position: (u64, BoardCellOption)) -> Result<(), IllegalMove> {
+        match self.ruleset.ko_rule() {
+            KoRule::Simple => {
+                // Recreating the position from before the opponent's last move
+                if self.history.len() >= 2 && self.history[self.history.len() - 2].0 == position.0 {
+                    return Err(IllegalMove::Ko);
+                }
+            },
+            KoRule::PositionalSuperko => {
+                if self.history.iter().any(|p| p.0 == position.0) {
+                    return Err(IllegalMove::Superko);
+                }
+            },
+            KoRule::SituationalSuperko => {
+                if self.history.contains(&position) {
+                    return Err(IllegalMove::Superko);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Zobrist hash of the stones on the board
+    pub fn hash(&self) -> u64 {
+        let mut h = 0;
+        for y in 0..self.size {
+            for x in 0..self.size {
+                h ^= zobrist_key(x, y, self.board[y][x]);
+            }
+        }
+        h
+    }
+
+    /// Forgets earlier positions, making the current one the only ko reference
+    pub fn reset_history(&mut self) {
+        self.history = vec![(self.hash(), self.to_move)];
+    }
+
+    /// Setup/edit path: places or removes a stone without turn or legality checks
+    pub fn set(& mut self, x: usize, y: usize, piece: BoardCellOption) {
+        if x < self.size && y < self.size {
+            self.board[y][x] = piece;
+            self.update(x, y);
+            self.reset_history();
+        }
+    }
+
+    fn update(& mut self, x: usize, y: usize) {
+        self.capture_neighbours(x, y);
+
+        let c = Cluster::from(self, x, y);
+        if c.color != BoardCellOption::None && !c.has_liberties(self) {
+            self.clear_cluster(&c);
+        }
+    }
+
+    /// Enemy groups adjacent to (x, y) are resolved before the stone's own group is checked for liberties
+    fn capture_neighbours(&mut self, x: usize, y: usize) -> Vec<[usize; 2]> {
+        let color = self.board[y][x];
+        let mut captured = vec![];
+
+        for [nx, ny] in self.neighbours(x, y) {
+            if self.board[ny][nx] != BoardCellOption::None && self.board[ny][nx] != color {
+                let c = Cluster::from(self, nx, ny);
+                if !c.has_liberties(self) {
+                    self.clear_cluster(&c);
+                    captured.extend(c.pieces);
+                }
+            }
+        }
+
+        captured
+    }
+
+    /// Orthogonally adjacent points that are on the board
+    pub fn neighbours(&self, x: usize, y: usize) -> Vec<[usize; 2]> {
+        let mut n = Vec::with_capacity(4);
+        if x.wrapping_sub(1) < self.size {
+            n.push([x - 1, y]);
+        }
+        if x + 1 < self.size {
+            n.push([x + 1, y]);
+        }
+        if y.wrapping_sub(1) < self.size {
+            n.push([x, y - 1]);
+        }
+        if y + 1 < self.size {
+            n.push([x, y + 1]);
+        }
+        n
+    }
+
+    fn clear_cluster(&mut self, c: &Cluster) {
+        match c.color {
+            BoardCellOption::Black => {
+                self.captured_white += c.pieces.len()
+            }, 
+            BoardCellOption::White => {
+                self.captured_black += c.pieces.len()
+            },
+            _ => {}
+        }
+        for p in &c.pieces {
+            self.board[p[1]][p[0]] = BoardCellOption::None;
+        }
+    }
+
+    /// Whether the stone at (x, y) has an empty neighbour
+    pub fn has_liberties(&self, x: usize, y: usize) -> bool {
+        self.value(x + 1, y) || 
+        self.value(x.wrapping_sub(1), y) || 
+        self.value(x, y + 1) || 
+        self.value(x, y.wrapping_sub(1))
+    }
+
+    fn value(&self, x: usize, y: usize) -> bool {
+        x < self.size && y < self.size && self.board[y][x] == BoardCellOption::None
+    }
+}
+
+/// A group of connected stones of one colour
+pub struct Cluster {
+    /// Points of the stones in the group
+    pub pieces: Vec<[usize; 2]>,
+    /// Colour of the group, None for an empty point
+    pub color: BoardCellOption
+}
+
+impl Cluster {
+    /// The group containing the point (x, y)
+    pub fn from(board: &GoBoard, x: usize, y: usize) -> Self {
+        let mut cl = Cluster { 
+            pieces: vec![[x, y]], 
+            color: board.board[y][x]
+        };
+
+        cl.next_piece(board, x, y.wrapping_sub(1));
+        cl.next_piece(board, x.wrapping_sub(1), y);
+        cl.next_piece(board, x + 1, y);
+        cl.next_piece(board, x, y + 1);
+
+        cl
+    }
+
+    fn next_piece(&mut self, board: &GoBoard, x: usize, y: usize) {
+        if x < board.size && y < board.size && board.board[y][x] == self.color && board.board[y][x] != BoardCellOption::None {
+            if !self.pieces.contains(&[x, y]) {
+                self.pieces.push([x, y]);
+            }
+
+            if !self.pieces.contains(&[x, y.wrapping_sub(1)]) { 
+                self.next_piece(board, x, y.wrapping_sub(1));
+            }
+            if !self.pieces.contains(&[x.wrapping_sub(1), y]) { 
+                self.next_piece(board, x.wrapping_sub(1), y);
+            }
+            if !self.pieces.contains(&[x + 1, y]) {
+                self.next_piece(board, x + 1, y);
+            }
+            if !self.pieces.contains(&[x, y + 1]) { 
+                self.next_piece(board, x, y + 1);
+            }
+        }
+    }
+
+    /// Whether any stone of the group has an empty neighbour
+    pub fn has_liberties(&self, board: &GoBoard) -> bool {
+        for p in &self.pieces {
+            if board.has_liberties(p[0], p[1]) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use BoardCellOption::{Black, White};
+
+    // Rows from the top, with X for black and O for white
+    fn position(rows: &[&str], ruleset: Ruleset) -> GoBoard {
+        let mut board = GoBoard::new(rows.len(), ruleset);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, point) in row.chars().enumerate() {
+                board.board[y][x] = match point {
+                    'X' => Black,
+                    'O' => White,
+                    _ => BoardCellOption::None
+                };
+            }
+        }
+        board.reset_history();
+        board
+    }
+
+    // Black to capture at E3, leaving White the ko at D3
+    const KO: [&str; 5] = [
+        ".....",
+        ".XO..",
+        "XO.O.",
+        ".XO..",
+        "....."
+    ];
+
+    #[test]
+    fn capture_comes_before_suicide() {
+        let mut board = position(&[".OX", "OX.", "..."], Ruleset::Japanese);
+        let outcome = board.play(Black, 0, 0).unwrap();
+        assert_eq!(outcome.captured, vec![[1, 0]]);
+        assert!(outcome.self_captured.is_empty());
+        assert_eq!(board.captured_black, 1);
+        assert_eq!(board.board[0][1], BoardCellOption::None);
+    }
+
+    #[test]
+    fn suicide_depends_on_the_rules() {
+        let mut board = position(&[".O.", "O..", "..."], Ruleset::Japanese);
+        assert_eq!(board.play(Black, 0, 0).unwrap_err(), IllegalMove::Suicide);
+        assert_eq!(board.board[0][0], BoardCellOption::None);
+        assert_eq!(board.to_move, Black);
+
+        // A lone stone would recreate the position, which superko forbids, so two stones are lost
+        let mut board = position(&["X.O", "OO.", "..."], Ruleset::TrompTaylor);
+        let mut outcome = board.play(Black, 1, 0).unwrap();
+        outcome.self_captured.sort();
+        assert_eq!(outcome.self_captured, vec![[0, 0], [1, 0]]);
+        assert_eq!(board.board[0][0], BoardCellOption::None);
+        assert_eq!(board.captured_white, 2);
+    }
+
+    #[test]
+    fn ko_cannot_be_retaken_at_once() {
+        let mut board = position(&KO, Ruleset::Japanese);
+        assert_eq!(board.play(Black, 2, 2).unwrap().captured, vec![[1, 2]]);
+        assert_eq!(board.play(White, 1, 2).unwrap_err(), IllegalMove::Ko);
+        assert_eq!(board.board[2][2], Black);
+
+        board.play(White, 4, 4).unwrap();
+        board.play(Black, 4, 0).unwrap();
+        assert_eq!(board.play(White, 1, 2).unwrap().captured, vec![[2, 2]]);
+    }
+
+    #[test]
+    fn superko_forbids_any_repetition() {
+        let mut board = position(&KO, Ruleset::Chinese);
+        board.play(Black, 2, 2).unwrap();
+        assert_eq!(board.play(White, 1, 2).unwrap_err(), IllegalMove::Superko);
+
+        // A position seen before with the other player to move only repeats positionally
+        let mut seen = GoBoard::new(5, Ruleset::Chinese);
+        seen.board[0][0] = Black;
+        let hash = seen.hash();
+
+        let mut positional = GoBoard::new(5, Ruleset::Chinese);
+        positional.history.push((hash, Black));
+        assert_eq!(positional.play(Black, 0, 0).unwrap_err(), IllegalMove::Superko);
+
+        let mut situational = GoBoard::new(5, Ruleset::Aga);
+        situational.history.push((hash, Black));
+        assert!(situational.play(Black, 0, 0).is_ok());
+
+        let mut situational = GoBoard::new(5, Ruleset::Aga);
+        situational.history.push((hash, White));
+        assert_eq!(situational.play(Black, 0, 0).unwrap_err(), IllegalMove::Superko);
+    }
+}
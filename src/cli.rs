@@ -0,0 +1,255 @@
+use go_rs::{handicap, BoardCellOption, Ruleset};
+use go_rs::clock::TimeControl;
+
+pub const USAGE: &str = "\
+Usage: go_rs [OPTIONS] [SIZE | FILE]
+
+Game:
+  --size N                 Board size, 2 to 25
+  --komi K                 Komi for White, e.g. 6.5
+  --rules NAME             japanese, korean, chinese, aga, nz or tromp-taylor
+  --handicap N             Fixed handicap of N stones on the star points
+  --free-handicap N        Black places N handicap stones anywhere
+  --time SPEC              absolute:600, byoyomi:600:30x5, canadian:600:300/25 or fischer:300+10
+  --load FILE              Open a .gs save or .sgf record instead of starting a new game
+  --review                 Start reviewing the loaded game from the first move
+
+Players:
+  --black human|engine CMD Who plays Black; CMD starts a GTP engine
+  --white human|engine CMD Who plays White
+  --engine COLOR CMD       Same as --COLOR engine CMD
+
+Interface:
+  --no-music               Do not play the tracks in the music folder or music.ogg
+  --theme NAME|FILE        green, kaya, dark, high-contrast, a theme in the themes folder
+                           or a JSON theme file; T switches themes while playing
+
+Other:
+  --gtp                    Speak GTP on stdin/stdout instead of opening a window;
+                           uses --size, --rules, --komi and --handicap
+  --help                   Show this help
+
+Size, rules and komi default to the settings in config.json in the config directory,
+which also holds the theme, volume and key bindings.
+";
+
+pub enum Player {
+    Human,
+    // Command line of a GTP engine
+    Engine(String)
+}
+
+pub struct Options {
+    pub size: Option<usize>,
+    pub komi: Option<f32>,
+    pub rules: Option<Ruleset>,
+    pub handicap: Option<usize>,
+    pub free_handicap: Option<usize>,
+    pub time: Option<TimeControl>,
+    pub load: Option<String>,
+    pub review: bool,
+    pub black: Player,
+    pub white: Player,
+    pub music: bool,
+    pub theme: Option<String>,
+    pub gtp: bool,
+    pub help: bool
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options { 
+            size: None, 
+            komi: None, 
+            rules: None, 
+            handicap: None, 
+            free_handicap: None, 
+            time: None, 
+            load: None, 
+            review: false, 
+            black: Player::Human, 
+            white: Player::Human, 
+            music: true, 
+            theme: None, 
+            gtp: false, 
+            help: false 
+        }
+    }
+}
+
+fn parse_size(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(size) if (2..=25).contains(&size) => Ok(size),
+        Ok(size) => Err(format!("board size must be between 2 and 25, not {}", size)),
+        Err(_) => Err(format!("'{}' is not a board size", value))
+    }
+}
+
+fn parse_player(kind: &str, args: &mut impl Iterator<Item = String>, option: &str) -> Result<Player, String> {
+    match kind {
+        "human" => Ok(Player::Human),
+        "engine" => match args.next() {
+            Some(command) if !command.trim().is_empty() => Ok(Player::Engine(command)),
+            _ => Err(format!("{} engine needs the command that starts the engine", option))
+        },
+        _ => Err(format!("{} takes 'human' or 'engine CMD', not '{}'", option, kind))
+    }
+}
+
+impl Options {
+    // Arguments without the program name; both "--size 9" and "--size=9" are accepted. Handicaps are
+    // checked against default_size, the configured board size, when no size is given.
+    pub fn parse(args: impl IntoIterator<Item = String>, default_size: usize) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) if arg.starts_with("--") => (name.to_string(), Some(value.to_string())),
+                _ => (arg.clone(), None)
+            };
+            let mut value = || inline.clone().or_else(|| args.next()).ok_or_else(|| format!("{} needs a value", name));
+
+            match name.as_str() {
+                "--size" => options.size = Some(parse_size(value()?.as_str())?),
+                "--komi" => {
+                    let komi = value()?;
+                    options.komi = Some(komi.parse::<f32>().ok().filter(|k| k.is_finite()).ok_or_else(|| format!("'{}' is not a komi", komi))?);
+                },
+                "--rules" => {
+                    let rules = value()?;
+                    options.rules = Some(Ruleset::from_name(rules.as_str()).ok_or_else(|| {
+                        format!("unknown rules '{}', use japanese, korean, chinese, aga, nz or tromp-taylor", rules)
+                    })?);
+                },
+                "--handicap" | "--free-handicap" => {
+                    let stones = value()?;
+                    let stones = stones.parse::<usize>().map_err(|_| format!("'{}' is not a number of stones", stones))?;
+                    if name == "--handicap" {
+                        options.handicap = Some(stones);
+                    } else {
+                        options.free_handicap = Some(stones);
+                    }
+                },
+                "--time" => {
+                    let spec = value()?;
+                    options.time = Some(TimeControl::parse(spec.as_str()).ok_or_else(|| format!("'{}' is not a time control", spec))?);
+                },
+                "--load" => options.load = Some(value()?),
+                "--review" => options.review = true,
+                "--black" => options.black = parse_player(value()?.as_str(), &mut args, "--black")?,
+                "--white" => options.white = parse_player(value()?.as_str(), &mut args, "--white")?,
+                "--engine" => {
+                    let color = value()?;
+                    let command = args.next().ok_or("--engine needs a colour and a command")?;
+                    match color.to_lowercase().as_str() {
+                        "b" | "black" => options.black = Player::Engine(command),
+                        "w" | "white" => options.white = Player::Engine(command),
+                        _ => return Err(format!("'{}' is not a colour", color))
+                    }
+                },
+                "--no-music" => options.music = false,
+                "--theme" => options.theme = Some(value()?),
+                "--gtp" => options.gtp = true,
+                "--help" | "-h" => options.help = true,
+                _ if name.starts_with('-') && name.len() > 1 => return Err(format!("unknown option {}", name)),
+                // A bare number is a board size, anything else a file to open
+                _ if arg.chars().all(|c| c.is_ascii_digit()) => options.size = Some(parse_size(arg.as_str())?),
+                _ if options.load.is_none() => options.load = Some(arg),
+                _ => return Err(format!("unexpected argument '{}'", arg))
+            }
+        }
+
+        options.check(default_size)?;
+        Ok(options)
+    }
+
+    fn check(&self, default_size: usize) -> Result<(), String> {
+        if self.load.is_some() && (self.size.is_some() || self.rules.is_some() || self.komi.is_some() || self.handicap.is_some() || self.free_handicap.is_some()) {
+            return Err(String::from("--size, --rules, --komi and handicaps only apply to new games, not with --load"));
+        }
+        if self.handicap.is_some() && self.free_handicap.is_some() {
+            return Err(String::from("--handicap and --free-handicap cannot be combined"));
+        }
+
+        let size = self.size.unwrap_or(default_size);
+        if let Some(stones) = self.handicap {
+            if handicap::fixed_handicap(size, stones).is_none() {
+                return Err(match handicap::max_handicap(size) {
+                    0 | 1 => format!("a {}x{} board cannot take a fixed handicap", size, size),
+                    max => format!("a {}x{} board takes 2 to {} fixed handicap stones, not {}", size, size, max, stones)
+                });
+            }
+        }
+        if let Some(stones) = self.free_handicap {
+            if stones < 2 || stones >= size * size {
+                return Err(format!("a {}x{} board takes 2 to {} free handicap stones, not {}", size, size, size * size - 1, stones));
+            }
+        }
+
+        if matches!((&self.black, &self.white), (Player::Engine(_), Player::Engine(_))) {
+            return Err(String::from("only one side can be played by an engine"));
+        }
+        if self.gtp && (self.engine().is_some() || self.load.is_some()) {
+            return Err(String::from("--gtp talks to a controller, it cannot load games or run engines itself"));
+        }
+        if self.gtp && (self.free_handicap.is_some() || self.time.is_some()) {
+            return Err(String::from("--free-handicap and --time do not apply to --gtp, the controller places stones and keeps time"));
+        }
+        if self.review && self.load.is_none() {
+            return Err(String::from("--review needs a game to load"));
+        }
+        Ok(())
+    }
+
+    // The side played by an engine, with its command line
+    pub fn engine(&self) -> Option<(BoardCellOption, &str)> {
+        match (&self.black, &self.white) {
+            (Player::Engine(command), _) => Some((BoardCellOption::Black, command.as_str())),
+            (_, Player::Engine(command)) => Some((BoardCellOption::White, command.as_str())),
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str, default_size: usize) -> Result<Options, String> {
+        Options::parse(args.split_whitespace().map(String::from), default_size)
+    }
+
+    #[test]
+    fn options_and_values() {
+        let options = parse("13 --komi 0.5 --rules tt --white engine gnugo", 19).unwrap();
+        assert_eq!(options.size, Some(13));
+        assert_eq!(options.komi, Some(0.5));
+        assert_eq!(options.rules, Some(Ruleset::TrompTaylor));
+        assert!(matches!(options.engine(), Some((BoardCellOption::White, "gnugo"))));
+
+        let options = parse("game.sgf --review", 19).unwrap();
+        assert_eq!(options.load.as_deref(), Some("game.sgf"));
+        assert!(parse("--size 26", 19).is_err());
+        assert!(parse("--komi nan", 19).is_err());
+        assert!(parse("--review", 19).is_err());
+    }
+
+    #[test]
+    fn handicaps_use_the_configured_size() {
+        assert!(parse("--handicap 9", 19).is_ok());
+        assert_eq!(parse("--handicap 5", 8).err(), Some(String::from("a 8x8 board takes 2 to 4 fixed handicap stones, not 5")));
+        assert_eq!(parse("--handicap 2", 5).err(), Some(String::from("a 5x5 board cannot take a fixed handicap")));
+        assert!(parse("--handicap 5 --size 19", 8).is_ok());
+        assert!(parse("--free-handicap 30", 5).is_err());
+        assert!(parse("--free-handicap 30", 19).is_ok());
+    }
+
+    #[test]
+    fn gtp_rejects_what_it_cannot_use() {
+        assert!(parse("--gtp --size 9 --handicap 4", 19).is_ok());
+        assert!(parse("--gtp --free-handicap 4", 19).is_err());
+        assert!(parse("--gtp --time absolute:600", 19).is_err());
+        assert!(parse("--gtp --black engine gnugo", 19).is_err());
+    }
+}
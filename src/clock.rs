@@ -0,0 +1,304 @@
+//! Game clocks for timed games.
+
+use serde::{Serialize, Deserialize};
+
+use crate::BoardCellOption;
+
+/// How much thinking time each player gets; all times are in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TimeControl {
+    /// A fixed amount of time for the whole game
+    Absolute {
+        /// Time for the whole game
+        main: f32
+    },
+    /// After main time, every move must be made within one period; running over one uses it up
+    ByoYomi {
+        /// Time before overtime starts
+        main: f32,
+        /// Length of one period
+        period: f32,
+        /// Number of periods
+        periods: u32
+    },
+    /// After main time, the given number of stones must be played within each period
+    Canadian {
+        /// Time before overtime starts
+        main: f32,
+        /// Length of one period
+        period: f32,
+        /// Stones to play within each period
+        stones: u32
+    },
+    /// Every move adds the increment to the remaining time
+    Fischer {
+        /// Time at the start of the game
+        main: f32,
+        /// Time added after each move
+        increment: f32
+    }
+}
+
+impl TimeControl {
+    /// Parses "absolute:600", "byoyomi:600:30x5", "canadian:600:300/25" or "fischer:300+10"
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (kind, rest) = spec.split_once(':')?;
+        let (main, overtime) = match rest.split_once(':') {
+            Some((main, overtime)) => (main, Some(overtime)),
+            None => (rest, None)
+        };
+
+        let control = match (kind.to_lowercase().as_str(), overtime) {
+            ("absolute", None) => TimeControl::Absolute { main: main.parse().ok()? },
+            ("byoyomi", Some(o)) => {
+                let (period, periods) = o.split_once('x')?;
+                TimeControl::ByoYomi { main: main.parse().ok()?, period: period.parse().ok()?, periods: periods.parse().ok()? }
+            },
+            ("canadian", Some(o)) => {
+                let (period, stones) = o.split_once('/')?;
+                TimeControl::Canadian { main: main.parse().ok()?, period: period.parse().ok()?, stones: stones.parse().ok()? }
+            },
+            ("fischer", None) => {
+                let (main, increment) = main.split_once('+')?;
+                TimeControl::Fischer { main: main.parse().ok()?, increment: increment.parse().ok()? }
+            },
+            _ => return None
+        };
+
+        let valid = match control {
+            TimeControl::Absolute { main } => main > 0.,
+            TimeControl::ByoYomi { main, period, periods } => main >= 0. && period > 0. && periods > 0,
+            TimeControl::Canadian { main, period, stones } => main >= 0. && period > 0. && stones > 0,
+            TimeControl::Fischer { main, increment } => main > 0. && increment >= 0.
+        };
+        if valid { Some(control) } else { None }
+    }
+
+    /// Time before overtime starts, or the starting time for Fischer
+    pub fn main_time(&self) -> f32 {
+        match self {
+            TimeControl::Absolute { main } |
+            TimeControl::ByoYomi { main, .. } |
+            TimeControl::Canadian { main, .. } |
+            TimeControl::Fischer { main, .. } => *main
+        }
+    }
+
+    /// SGF OT property text describing the overtime, if there is any
+    pub fn overtime(&self) -> Option<String> {
+        match self {
+            TimeControl::Absolute { .. } => None,
+            TimeControl::ByoYomi { period, periods, .. } => Some(format!("{}x{} byo-yomi", periods, period)),
+            TimeControl::Canadian { period, stones, .. } => Some(format!("{}/{} Canadian", stones, period)),
+            TimeControl::Fischer { increment, .. } => Some(format!("{} fischer", increment))
+        }
+    }
+}
+
+/// Remaining time of one player
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlayerClock {
+    /// Main time left
+    pub main: f32,
+    /// Whether main time is used up and overtime periods are running
+    pub overtime: bool,
+    /// Time left in the current overtime period
+    pub period: f32,
+    /// Byo-yomi periods left
+    pub periods: u32,
+    /// Canadian stones still to play in the current period
+    pub stones: u32,
+    /// Whether the player ran out of time
+    pub flagged: bool
+}
+
+impl PlayerClock {
+    fn new(control: &TimeControl) -> Self {
+        let (period, periods, stones) = match *control {
+            TimeControl::ByoYomi { period, periods, .. } => (period, periods, 0),
+            TimeControl::Canadian { period, stones, .. } => (period, 0, stones),
+            _ => (0., 0, 0)
+        };
+        PlayerClock {
+            main: control.main_time(),
+            overtime: false,
+            period,
+            periods,
+            stones,
+            flagged: false
+        }
+    }
+}
+
+fn format_time(seconds: f32) -> String {
+    let s = seconds.max(0.).ceil() as u32;
+    format!("{}:{:02}", s / 60, s % 60)
+}
+
+/// Clocks of both players under one time control
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameClock {
+    /// Time control the clocks were started with
+    pub control: TimeControl,
+    /// Black's clock
+    pub black: PlayerClock,
+    /// White's clock
+    pub white: PlayerClock
+}
+
+impl GameClock {
+    /// Both clocks at the start of the game
+    pub fn new(control: TimeControl) -> Self {
+        GameClock {
+            control,
+            black: PlayerClock::new(&control),
+            white: PlayerClock::new(&control)
+        }
+    }
+
+    /// The clock of the given player
+    pub fn player(&self, color: BoardCellOption) -> &PlayerClock {
+        if color == BoardCellOption::White { &self.white } else { &self.black }
+    }
+
+    fn player_mut(&mut self, color: BoardCellOption) -> &mut PlayerClock {
+        if color == BoardCellOption::White { &mut self.white } else { &mut self.black }
+    }
+
+    /// Runs the clock of the player to move, returning true when they run out of time
+    pub fn tick(&mut self, color: BoardCellOption, delta: f32) -> bool {
+        let control = self.control;
+        let clock = self.player_mut(color);
+        if clock.flagged {
+            return true;
+        }
+
+        let mut delta = delta;
+        if !clock.overtime {
+            clock.main -= delta;
+            if clock.main > 0. {
+                return false;
+            }
+            delta = -clock.main;
+            clock.main = 0.;
+            match control {
+                TimeControl::ByoYomi { .. } | TimeControl::Canadian { .. } => clock.overtime = true,
+                _ => {
+                    clock.flagged = true;
+                    return true;
+                }
+            }
+        }
+
+        clock.period -= delta;
+        while clock.period <= 0. {
+            match control {
+                TimeControl::ByoYomi { period, .. } if clock.periods > 1 => {
+                    clock.periods -= 1;
+                    clock.period += period;
+                },
+                _ => {
+                    clock.period = 0.;
+                    clock.flagged = true;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Called after the player made a move
+    pub fn on_move(&mut self, color: BoardCellOption) {
+        let control = self.control;
+        let clock = self.player_mut(color);
+        if clock.flagged {
+            return;
+        }
+
+        match control {
+            TimeControl::ByoYomi { period, .. } if clock.overtime => clock.period = period,
+            TimeControl::Canadian { period, stones, .. } if clock.overtime => {
+                clock.stones = clock.stones.saturating_sub(1);
+                if clock.stones == 0 {
+                    clock.period = period;
+                    clock.stones = stones;
+                }
+            },
+            TimeControl::Fischer { increment, .. } => clock.main += increment,
+            _ => {}
+        }
+    }
+
+    /// Main time, or the overtime state once main time is used up
+    pub fn display(&self, color: BoardCellOption) -> (String, String) {
+        let clock = self.player(color);
+        if !clock.overtime {
+            let extra = match self.control {
+                TimeControl::ByoYomi { period, periods, .. } => format!("{}x{}", periods, format_time(period)),
+                TimeControl::Canadian { period, stones, .. } => format!("{}/{}", stones, format_time(period)),
+                TimeControl::Fischer { increment, .. } => format!("+{}s", increment),
+                TimeControl::Absolute { .. } => String::new()
+            };
+            return (format_time(clock.main), extra);
+        }
+
+        match self.control {
+            TimeControl::ByoYomi { .. } => (format_time(clock.period), format!("{} periods", clock.periods)),
+            TimeControl::Canadian { .. } => (format_time(clock.period), format!("{} stones", clock.stones)),
+            _ => (format_time(clock.main), String::new())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use BoardCellOption::{Black, White};
+
+    #[test]
+    fn time_controls_parse() {
+        assert_eq!(TimeControl::parse("absolute:600"), Some(TimeControl::Absolute { main: 600. }));
+        assert_eq!(TimeControl::parse("ByoYomi:0:30x5"), Some(TimeControl::ByoYomi { main: 0., period: 30., periods: 5 }));
+        assert_eq!(TimeControl::parse("canadian:600:300/25"), Some(TimeControl::Canadian { main: 600., period: 300., stones: 25 }));
+        assert_eq!(TimeControl::parse("fischer:300+10"), Some(TimeControl::Fischer { main: 300., increment: 10. }));
+        for spec in ["absolute:0", "byoyomi:600", "byoyomi:600:30x0", "fischer:300", "sudden:60", "600"] {
+            assert_eq!(TimeControl::parse(spec), None, "{}", spec);
+        }
+    }
+
+    #[test]
+    fn byoyomi_uses_up_periods() {
+        let mut clock = GameClock::new(TimeControl::ByoYomi { main: 10., period: 5., periods: 2 });
+        assert!(!clock.tick(Black, 12.));
+        assert!(clock.black.overtime);
+        assert_eq!(clock.black.period, 3.);
+
+        clock.on_move(Black);
+        assert_eq!(clock.black.period, 5.);
+        assert!(!clock.tick(Black, 6.));
+        assert_eq!(clock.black.periods, 1);
+        assert!(clock.tick(Black, 5.));
+        assert!(clock.black.flagged);
+        assert_eq!(clock.white.main, 10.);
+    }
+
+    #[test]
+    fn canadian_resets_after_enough_stones() {
+        let mut clock = GameClock::new(TimeControl::Canadian { main: 0., period: 60., stones: 2 });
+        assert!(!clock.tick(White, 20.));
+        clock.on_move(White);
+        assert_eq!((clock.white.period, clock.white.stones), (40., 1));
+        clock.on_move(White);
+        assert_eq!((clock.white.period, clock.white.stones), (60., 2));
+    }
+
+    #[test]
+    fn fischer_adds_the_increment() {
+        let mut clock = GameClock::new(TimeControl::Fischer { main: 30., increment: 10. });
+        clock.tick(Black, 25.);
+        clock.on_move(Black);
+        assert_eq!(clock.black.main, 15.);
+        assert!(clock.tick(Black, 15.));
+    }
+}
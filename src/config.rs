@@ -0,0 +1,341 @@
+//! User preferences kept in `config.json` in the config directory.
+//!
+//! Every field has a default, so a partial or missing file still gives a complete configuration,
+//! and settings added in later versions are filled in when an older file is loaded.
+
+use std::collections::BTreeMap;
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+
+use serde::{Serialize, Deserialize};
+
+use crate::board::Ruleset;
+use crate::error::{read_file, write_file, FileError};
+use crate::paths::config_dir;
+use crate::theme::BUILTIN_THEMES;
+
+/// Location of the configuration file
+pub fn config_path() -> PathBuf {
+    config_dir().join("config.json")
+}
+
+/// Where [`Config::back_up`] keeps a configuration file that could not be loaded
+pub fn backup_path() -> PathBuf {
+    config_dir().join("config.json.bak")
+}
+
+/// Something the player can do with a key press
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Pass the turn
+    Pass,
+    /// Resign the game
+    Resign,
+    /// Take back the last move
+    Undo,
+    /// Replay a move taken back
+    Redo,
+    /// Enter or leave review mode
+    Review,
+    /// Save to the current file
+    Save,
+    /// Save under a new name
+    SaveAs,
+    /// Pick a recent file to open
+    Open,
+    /// Write the game as SGF
+    Export,
+    /// Switch to the previous variation
+    PreviousVariation,
+    /// Switch to the next variation
+    NextVariation,
+    /// Delete the current branch
+    DeleteBranch,
+    /// Black accepts the dead stones while scoring
+    AgreeBlack,
+    /// White accepts the dead stones while scoring
+    AgreeWhite,
+    /// Go back from scoring to playing
+    ResumePlay,
+    /// Step back one move in review
+    Back,
+    /// Step forward one move in review
+    Forward,
+    /// Jump to the start in review
+    First,
+    /// Jump to the end of the line in review
+    Last,
+    /// Start or stop autoplay in review
+    Autoplay,
+    /// Autoplay faster
+    Faster,
+    /// Autoplay slower
+    Slower,
+    /// Switch where coordinates are drawn
+    Coordinates,
+    /// Switch to the next theme
+    Theme,
+    /// Silence or restore music and sound effects
+    Mute,
+    /// Turn the music up
+    MusicLouder,
+    /// Turn the music down
+    MusicQuieter,
+    /// Turn the sound effects up
+    EffectsLouder,
+    /// Turn the sound effects down
+    EffectsQuieter,
+    /// Skip to the next track of the playlist
+    NextTrack,
+    /// Switch between playing the playlist in order and shuffled
+    Shuffle,
+    /// Show or hide the audio settings
+    AudioSettings
+}
+
+impl Action {
+    /// Every action, in the order they are listed in the configuration file
+    pub const ALL: [Action; 32] = [
+        Action::Pass, Action::Resign, Action::Undo, Action::Redo, Action::Review,
+        Action::Save, Action::SaveAs, Action::Open, Action::Export,
+        Action::PreviousVariation, Action::NextVariation, Action::DeleteBranch,
+        Action::AgreeBlack, Action::AgreeWhite, Action::ResumePlay,
+        Action::Back, Action::Forward, Action::First, Action::Last,
+        Action::Autoplay, Action::Faster, Action::Slower, Action::Coordinates, Action::Theme,
+        Action::Mute, Action::MusicLouder, Action::MusicQuieter, Action::EffectsLouder,
+        Action::EffectsQuieter, Action::NextTrack, Action::Shuffle, Action::AudioSettings
+    ];
+
+    /// Keys bound to the action unless the configuration says otherwise.
+    ///
+    /// Alternatives are separated by commas and modifiers are joined with `+`, as in `"Ctrl+Z, Backspace"`.
+    pub fn default_keys(&self) -> &'static str {
+        match self {
+            Action::Pass => "P",
+            Action::Resign => "Shift+R",
+            Action::Undo => "Ctrl+Z, Backspace",
+            Action::Redo => "Ctrl+Y, Ctrl+Shift+Z",
+            Action::Review => "V",
+            Action::Save => "S",
+            Action::SaveAs => "Ctrl+S",
+            Action::Open => "Ctrl+O",
+            Action::Export => "E",
+            Action::PreviousVariation => "Up",
+            Action::NextVariation => "Down",
+            Action::DeleteBranch => "Delete",
+            Action::AgreeBlack => "B",
+            Action::AgreeWhite => "W",
+            Action::ResumePlay => "Escape",
+            Action::Back => "Left",
+            Action::Forward => "Right",
+            Action::First => "Home",
+            Action::Last => "End",
+            Action::Autoplay => "Space",
+            Action::Faster => "Equal, KpAdd",
+            Action::Slower => "Minus, KpSubtract",
+            Action::Coordinates => "C",
+            Action::Theme => "T",
+            Action::Mute => "M",
+            Action::MusicLouder => "RightBracket",
+            Action::MusicQuieter => "LeftBracket",
+            Action::EffectsLouder => "Shift+RightBracket",
+            Action::EffectsQuieter => "Shift+LeftBracket",
+            Action::NextTrack => "N",
+            Action::Shuffle => "Shift+N",
+            Action::AudioSettings => "A"
+        }
+    }
+}
+
+/// Sides of the board that show coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Coordinates {
+    /// No coordinates
+    Hidden,
+    /// Letters above and numbers to the left
+    #[default]
+    TwoSides,
+    /// Letters above and below, numbers on both sides
+    AllSides
+}
+
+impl Coordinates {
+    /// The setting after this one, cycling back to [`Coordinates::Hidden`]
+    pub fn next(&self) -> Self {
+        match self {
+            Coordinates::Hidden => Coordinates::TwoSides,
+            Coordinates::TwoSides => Coordinates::AllSides,
+            Coordinates::AllSides => Coordinates::Hidden
+        }
+    }
+}
+
+/// Preferences loaded at startup and stored whenever they change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Board size for new games
+    pub size: usize,
+    /// Rules for new games
+    pub ruleset: Ruleset,
+    /// Komi for new games, or `None` for the ruleset's default
+    pub komi: Option<f32>,
+    /// Theme name or path to a theme file, as understood by [`Theme::find`](crate::theme::Theme::find)
+    pub theme: String,
+    /// Where coordinates are drawn
+    pub coordinates: Coordinates,
+    /// Whether background music plays
+    pub music: bool,
+    /// Folder of tracks to play; `music.ogg` plays when it has none
+    pub music_dir: String,
+    /// Whether the playlist is played in random order
+    pub shuffle: bool,
+    /// Music volume from 0 to 1
+    pub music_volume: f32,
+    /// Whether sound effects play
+    pub sounds: bool,
+    /// Sound effect volume from 0 to 1
+    pub effects_volume: f32,
+    /// Silences music and sound effects without changing their volumes
+    pub muted: bool,
+    /// Window width in pixels
+    pub window_width: i32,
+    /// Window height in pixels
+    pub window_height: i32,
+    /// Moves between autosaves; 0 turns autosave off
+    pub autosave_moves: usize,
+    /// Keys for each action; actions left out keep their [`Action::default_keys`]
+    pub keys: BTreeMap<Action, String>
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            size: 19,
+            ruleset: Ruleset::default(),
+            komi: None,
+            theme: String::from(BUILTIN_THEMES[0]),
+            coordinates: Coordinates::default(),
+            music: true,
+            music_dir: String::from("music"),
+            shuffle: false,
+            music_volume: 1.0,
+            sounds: true,
+            effects_volume: 1.0,
+            muted: false,
+            window_width: 800,
+            window_height: 800,
+            autosave_moves: 5,
+            keys: Action::ALL.iter().map(|a| (*a, a.default_keys().to_string())).collect()
+        }
+    }
+}
+
+impl Config {
+    /// The stored configuration, or the defaults if there is no file yet
+    pub fn load() -> Result<Self, FileError> {
+        let path = config_path().to_string_lossy().into_owned();
+        if !config_path().exists() {
+            return Ok(Config::default());
+        }
+
+        let text = read_file(path.as_str())?;
+        let invalid = |reason: String| Err(FileError::Invalid { path: path.clone(), reason });
+        let config: Config = match serde_json::from_str(text.as_str()) {
+            Ok(config) => config,
+            Err(e) => return invalid(e.to_string())
+        };
+
+        if !(2..=25).contains(&config.size) {
+            return invalid(format!("board size {} is not between 2 and 25", config.size));
+        }
+        if config.komi.is_some_and(|k| !k.is_finite()) {
+            return invalid(String::from("komi is not a number"));
+        }
+        if !(0.0..=1.0).contains(&config.music_volume) {
+            return invalid(format!("music volume {} is not between 0 and 1", config.music_volume));
+        }
+        if !(0.0..=1.0).contains(&config.effects_volume) {
+            return invalid(format!("effects volume {} is not between 0 and 1", config.effects_volume));
+        }
+        if config.window_width < 100 || config.window_height < 100 {
+            return invalid(format!("window size {}x{} is too small", config.window_width, config.window_height));
+        }
+        if config.autosave_moves > 1000 {
+            return invalid(format!("autosave every {} moves is more than 1000", config.autosave_moves));
+        }
+        Ok(config)
+    }
+
+    /// Writes the configuration, creating the config directory if needed
+    pub fn save(&self) -> Result<(), FileError> {
+        let dir = config_dir();
+        create_dir_all(&dir).map_err(|source| FileError::Io { path: dir.to_string_lossy().into_owned(), source })?;
+
+        let path = config_path().to_string_lossy().into_owned();
+        let text = serde_json::to_string_pretty(self).map_err(|source| FileError::Save { path: path.clone(), source })?;
+        write_file(path.as_str(), text.as_str())
+    }
+
+    /// Copies the configuration file to [`backup_path`], so that saving after a failed
+    /// [`Config::load`] does not lose the player's settings
+    pub fn back_up() -> Result<PathBuf, FileError> {
+        let backup = backup_path();
+        std::fs::copy(config_path(), &backup)
+            .map_err(|source| FileError::Io { path: backup.to_string_lossy().into_owned(), source })?;
+        Ok(backup)
+    }
+
+    /// Komi for new games under the configured rules
+    pub fn komi(&self) -> f32 {
+        self.komi.unwrap_or_else(|| self.ruleset.default_komi())
+    }
+
+    /// Keys bound to an action, as written in the configuration
+    pub fn keys(&self, action: Action) -> &str {
+        self.keys.get(&action).map(|k| k.as_str()).unwrap_or_else(|| action.default_keys())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_fall_back_to_defaults() {
+        let mut config = Config::default();
+        config.keys.remove(&Action::Pass);
+        config.keys.insert(Action::Undo, String::from("U"));
+        assert_eq!(config.keys(Action::Pass), "P");
+        assert_eq!(config.keys(Action::Undo), "U");
+        assert_eq!(config.komi(), 6.5);
+    }
+
+    #[test]
+    fn partial_files_keep_defaults() {
+        let config: Config = serde_json::from_str(r#"{"size": 9, "keys": {"pass": "Space"}}"#).unwrap();
+        assert_eq!(config.size, 9);
+        assert_eq!(config.keys(Action::Pass), "Space");
+        assert_eq!(config.music_volume, 1.0);
+        assert_eq!(config.autosave_moves, 5);
+    }
+
+    #[test]
+    fn invalid_files_are_reported_and_backed_up() {
+        let dir = crate::paths::tests::home();
+        std::fs::write(config_path(), r#"{"size": 40}"#).unwrap();
+        assert!(matches!(Config::load(), Err(FileError::Invalid { .. })));
+
+        assert_eq!(Config::back_up().unwrap(), dir.join("config.json.bak"));
+        Config::default().save().unwrap();
+        assert_eq!(std::fs::read_to_string(backup_path()).unwrap(), r#"{"size": 40}"#);
+        assert_eq!(Config::load().unwrap(), Config::default());
+
+        std::fs::write(config_path(), r#"{"autosave_moves": 5000}"#).unwrap();
+        assert!(matches!(Config::load(), Err(FileError::Invalid { .. })));
+        std::fs::write(config_path(), r#"{"autosave_moves": 0}"#).unwrap();
+        assert_eq!(Config::load().unwrap().autosave_moves, 0);
+    }
+}
@@ -0,0 +1,88 @@
+//! Board coordinates as Go players write them, such as "D4" or "Q16".
+//!
+//! Columns are lettered from the left, skipping I so it cannot be mistaken for J or 1, and rows
+//! are numbered from the bottom. Internally points are (x, y) with y counted from the top, so
+//! every place that shows or reads a point goes through this module.
+
+use crate::game::Move;
+
+/// Column letters; boards wider than this continue with two letters, "AA", "AB" and so on
+pub const COLUMNS: &[u8] = b"ABCDEFGHJKLMNOPQRSTUVWXYZ";
+
+/// Letters of column x
+pub fn column_label(x: usize) -> String {
+    if x < COLUMNS.len() {
+        (COLUMNS[x] as char).to_string()
+    } else {
+        let n = COLUMNS.len();
+        format!("{}{}", COLUMNS[x / n - 1] as char, COLUMNS[x % n] as char)
+    }
+}
+
+/// Number of row y on a board of the given size, counted from the bottom
+pub fn row_label(y: usize, size: usize) -> String {
+    (size - y).to_string()
+}
+
+/// Name of the point (x, y), such as "D4"
+pub fn format_point(x: usize, y: usize, size: usize) -> String {
+    format!("{}{}", column_label(x), row_label(y, size))
+}
+
+/// A move as "D4", "pass" or "resign"
+pub fn format_move(mv: Move, size: usize) -> String {
+    match mv {
+        Move::Play(x, y) => format_point(x, y, size),
+        Move::Pass => String::from("pass"),
+        Move::Resign => String::from("resign")
+    }
+}
+
+/// Reads a point name in either case, or None if it is not on a board of the given size
+pub fn parse_point(text: &str, size: usize) -> Option<[usize; 2]> {
+    let text = text.trim().to_ascii_uppercase();
+    let digits = text.find(|c: char| c.is_ascii_digit())?;
+    let (letters, row) = text.split_at(digits);
+
+    let letter = |c: u8| COLUMNS.iter().position(|l| *l == c);
+    let x = match letters.as_bytes() {
+        [c] => letter(*c)?,
+        [first, second] => (letter(*first)? + 1) * COLUMNS.len() + letter(*second)?,
+        _ => return None
+    };
+    let row = row.parse::<usize>().ok()?;
+
+    (x < size && (1..=size).contains(&row)).then(|| [x, size - row])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_skip_i() {
+        assert_eq!(format_point(0, 18, 19), "A1");
+        assert_eq!(format_point(8, 0, 19), "J19");
+        assert_eq!(format_point(25, 0, 30), "AA30");
+        assert_eq!(format_move(Move::Pass, 19), "pass");
+    }
+
+    #[test]
+    fn parsing_reverses_formatting() {
+        for size in [5, 19, 30] {
+            for x in 0..size {
+                for y in 0..size {
+                    assert_eq!(parse_point(format_point(x, y, size).as_str(), size), Some([x, y]));
+                }
+            }
+        }
+        assert_eq!(parse_point(" q16 ", 19), Some([15, 3]));
+    }
+
+    #[test]
+    fn points_off_the_board_are_rejected() {
+        for text in ["I5", "T20", "U1", "A0", "A", "5", "", "ABC1"] {
+            assert_eq!(parse_point(text, 19), None, "{}", text);
+        }
+    }
+}
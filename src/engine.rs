@@ -0,0 +1,340 @@
+//! Playing against external engines that speak GTP.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread;
+
+use crate::{BoardCellOption, IllegalMove};
+use crate::game::{Game, Move};
+use crate::gtp::{format_vertex, parse_vertex};
+
+/// Failure while talking to an external engine
+#[derive(Debug)]
+pub enum EngineError {
+    /// The engine process could not be started
+    Spawn(io::Error),
+    /// Reading from or writing to the engine failed
+    Io(io::Error),
+    /// The engine closed its output
+    Crashed,
+    /// The engine answered a command with a GTP failure response
+    Failure(String),
+    /// A reply could not be understood
+    BadReply(String),
+    /// The engine played the vertex, which the rules rejected
+    IllegalMove(String, IllegalMove)
+}
+
+impl std::fmt::Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::Spawn(e) => write!(f, "Could not start engine: {}", e),
+            EngineError::Io(e) => write!(f, "Engine communication failed: {}", e),
+            EngineError::Crashed => write!(f, "Engine exited unexpectedly"),
+            EngineError::Failure(message) => write!(f, "Engine error: {}", message),
+            EngineError::BadReply(reply) => write!(f, "Engine sent an invalid reply: {}", reply),
+            EngineError::IllegalMove(vertex, e) => write!(f, "Engine played an illegal move {}: {}", vertex, e)
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+/// A GTP engine running as a child process
+pub struct GtpClient {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>
+}
+
+impl GtpClient {
+    /// Starts the engine from a command line split on whitespace
+    pub fn spawn(command_line: &str) -> Result<Self, EngineError> {
+        let mut parts = command_line.split_whitespace();
+        let program = parts.next().ok_or_else(|| EngineError::Spawn(io::Error::new(io::ErrorKind::InvalidInput, "empty engine command")))?;
+
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(EngineError::Spawn)?;
+
+        let stdin = child.stdin.take().ok_or(EngineError::Crashed)?;
+        let stdout = BufReader::new(child.stdout.take().ok_or(EngineError::Crashed)?);
+
+        Ok(GtpClient { child, stdin, stdout })
+    }
+
+    /// Sends one command and waits for the response text
+    pub fn send(&mut self, command: &str) -> Result<String, EngineError> {
+        writeln!(self.stdin, "{}", command).and_then(|_| self.stdin.flush()).map_err(|e| match e.kind() {
+            io::ErrorKind::BrokenPipe => EngineError::Crashed,
+            _ => EngineError::Io(e)
+        })?;
+
+        // The response ends with an empty line
+        let mut lines = vec![];
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line).map_err(EngineError::Io)? == 0 {
+                return Err(EngineError::Crashed);
+            }
+            let line = line.trim_end().to_string();
+            if line.is_empty() {
+                if lines.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            lines.push(line);
+        }
+
+        let response = lines.join("\n");
+        let body = |rest: &str| rest.trim_start_matches(|c: char| c.is_ascii_digit()).trim().to_string();
+        if let Some(rest) = response.strip_prefix('=') {
+            Ok(body(rest))
+        } else if let Some(rest) = response.strip_prefix('?') {
+            Err(EngineError::Failure(body(rest)))
+        } else {
+            Err(EngineError::BadReply(response))
+        }
+    }
+}
+
+impl Drop for GtpClient {
+    fn drop(&mut self) {
+        let _ = writeln!(self.stdin, "quit");
+        let _ = self.stdin.flush();
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+enum Request {
+    Command(String),
+    GenMove(BoardCellOption)
+}
+
+/// Talks to the engine on a separate thread so the window keeps drawing while it thinks
+pub struct EnginePlayer {
+    /// Colour the engine plays
+    pub color: BoardCellOption,
+    requests: Sender<Request>,
+    replies: Receiver<Result<String, EngineError>>,
+    thinking: bool
+}
+
+impl EnginePlayer {
+    /// Starts the engine and sends it the game so far.
+    ///
+    /// The position is sent from the engine's thread, so an engine that never answers cannot
+    /// freeze the caller; a failure shows up later from [`EnginePlayer::poll`].
+    pub fn spawn(command_line: &str, color: BoardCellOption, game: &Game) -> Result<Self, EngineError> {
+        let mut client = GtpClient::spawn(command_line)?;
+
+        let (requests, thread_requests) = channel::<Request>();
+        let (thread_replies, replies) = channel();
+        for command in position_commands(game) {
+            let _ = requests.send(Request::Command(command));
+        }
+
+        thread::spawn(move || {
+            for request in thread_requests {
+                let result = match request {
+                    Request::Command(command) => match client.send(command.as_str()) {
+                        Ok(_) => continue,
+                        Err(e) => Err(e)
+                    },
+                    Request::GenMove(color) => {
+                        client.send(if color == BoardCellOption::White { "genmove w" } else { "genmove b" })
+                    }
+                };
+                let failed = result.is_err();
+                if thread_replies.send(result).is_err() || failed {
+                    break;
+                }
+            }
+        });
+
+        Ok(EnginePlayer { color, requests, replies, thinking: false })
+    }
+
+    /// Tells the engine about a move made by the other side
+    pub fn notify(&mut self, color: BoardCellOption, mv: Move, size: usize) {
+        if let Some(command) = play_command(color, mv, size) {
+            let _ = self.requests.send(Request::Command(command));
+        }
+    }
+
+    /// Resends the whole position, after jumping around in the game tree
+    pub fn sync(&mut self, game: &Game) {
+        for command in position_commands(game) {
+            let _ = self.requests.send(Request::Command(command));
+        }
+    }
+
+    /// Takes back the engine's copy of the last move
+    pub fn undo(&mut self) {
+        let _ = self.requests.send(Request::Command(String::from("undo")));
+    }
+
+    /// Asks for a move unless the engine is already thinking
+    pub fn request_move(&mut self) {
+        if !self.thinking {
+            self.thinking = self.requests.send(Request::GenMove(self.color)).is_ok();
+        }
+    }
+
+    /// Whether a requested move has not arrived yet
+    pub fn is_thinking(&self) -> bool {
+        self.thinking
+    }
+
+    /// Returns the engine's move once it has answered
+    pub fn poll(&mut self, size: usize) -> Option<Result<Move, EngineError>> {
+        let reply = match self.replies.try_recv() {
+            Ok(reply) => reply,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(EngineError::Crashed)
+        };
+        self.thinking = false;
+
+        Some(reply.and_then(|vertex| {
+            if vertex.eq_ignore_ascii_case("resign") {
+                return Ok(Move::Resign);
+            }
+            match parse_vertex(vertex.as_str(), size) {
+                Ok(Some([x, y])) => Ok(Move::Play(x, y)),
+                Ok(None) => Ok(Move::Pass),
+                Err(_) => Err(EngineError::BadReply(vertex))
+            }
+        }))
+    }
+}
+
+/// Commands that set up the engine's board like the game's current position.
+///
+/// Moves are replayed when the line only has stones added at the root, so the engine knows the ko
+/// history. GTP has no command to clear a point, so any other setup sends the current stones instead.
+fn position_commands(game: &Game) -> Vec<String> {
+    let size = game.board.size;
+    let mut commands = vec![
+        format!("boardsize {}", size),
+        String::from("clear_board"),
+        format!("komi {}", game.komi)
+    ];
+
+    let path = game.tree.path(game.current);
+    let replayable = path.iter().all(|&n| {
+        let setup = &game.tree.nodes[n].setup;
+        setup.empty.is_empty() && (game.tree.nodes[n].parent.is_none() || (setup.black.is_empty() && setup.white.is_empty()))
+    });
+    if !replayable {
+        // Black first, then White: no partial position can capture or be suicide when the whole one is legal
+        for color in [BoardCellOption::Black, BoardCellOption::White] {
+            for (y, row) in game.board.board.iter().enumerate() {
+                for (x, point) in row.iter().enumerate() {
+                    if *point == color {
+                        commands.extend(play_command(color, Move::Play(x, y), size));
+                    }
+                }
+            }
+        }
+        return commands;
+    }
+
+    for n in path {
+        let node = &game.tree.nodes[n];
+        for &[x, y] in &node.setup.black {
+            commands.extend(play_command(BoardCellOption::Black, Move::Play(x, y), size));
+        }
+        for &[x, y] in &node.setup.white {
+            commands.extend(play_command(BoardCellOption::White, Move::Play(x, y), size));
+        }
+        if let Some((color, mv)) = node.mv {
+            commands.extend(play_command(color, mv, size));
+        }
+    }
+    commands
+}
+
+fn play_command(color: BoardCellOption, mv: Move, size: usize) -> Option<String> {
+    let c = if color == BoardCellOption::White { "w" } else { "b" };
+    match mv {
+        Move::Play(x, y) => Some(format!("play {} {}", c, format_vertex(x, y, size))),
+        Move::Pass => Some(format!("play {} pass", c)),
+        Move::Resign => None
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::{GoBoard, Ruleset};
+
+    // A stand-in engine: a shell script answering each command from a case statement
+    fn fake_engine(name: &str, cases: &str) -> String {
+        let path = std::env::temp_dir().join(format!("go_rs_engine_{}_{}.sh", name, std::process::id()));
+        let script = format!("while read -r line; do\n  case \"$line\" in\n{}\n    *) printf '=\\n\\n' ;;\n  esac\ndone\n", cases);
+        std::fs::write(&path, script).unwrap();
+        format!("sh {}", path.display())
+    }
+
+    fn wait(player: &mut EnginePlayer) -> Result<Move, EngineError> {
+        for _ in 0..500 {
+            if let Some(reply) = player.poll(9) {
+                return reply;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        panic!("engine did not answer");
+    }
+
+    #[test]
+    fn replies_are_parsed() {
+        let mut client = GtpClient::spawn(fake_engine("replies", "    name*) printf '=1 fake\\n\\n' ;;\n    boom*) printf '? no good\\n\\n' ;;").as_str()).unwrap();
+        assert_eq!(client.send("name").unwrap(), "fake");
+        assert!(matches!(client.send("boom"), Err(EngineError::Failure(m)) if m == "no good"));
+        assert_eq!(client.send("anything").unwrap(), "");
+    }
+
+    #[test]
+    fn multibyte_reply_is_an_error() {
+        let mut client = GtpClient::spawn(fake_engine("multibyte", "    bad*) printf '\\303\\251 bad\\n\\n' ;;").as_str()).unwrap();
+        assert!(matches!(client.send("bad"), Err(EngineError::BadReply(r)) if r == "é bad"));
+    }
+
+    #[test]
+    fn engine_moves_are_returned() {
+        let game = Game::new(GoBoard::new(9, Ruleset::default()));
+        let command = fake_engine("genmove", "    genmove*) printf '= D4\\n\\n' ;;");
+        let mut player = EnginePlayer::spawn(command.as_str(), BoardCellOption::Black, &game).unwrap();
+        player.request_move();
+        assert!(player.is_thinking());
+        assert_eq!(wait(&mut player).unwrap(), Move::Play(3, 5));
+        assert!(!player.is_thinking());
+    }
+
+    #[test]
+    fn silent_engine_does_not_block_spawn() {
+        let game = Game::new(GoBoard::new(9, Ruleset::default()));
+        let path = std::env::temp_dir().join(format!("go_rs_engine_silent_{}.sh", std::process::id()));
+        std::fs::write(&path, "cat > /dev/null\n").unwrap();
+        let mut player = EnginePlayer::spawn(format!("sh {}", path.display()).as_str(), BoardCellOption::White, &game).unwrap();
+        assert!(player.poll(9).is_none());
+    }
+
+    #[test]
+    fn cleared_points_send_the_position() {
+        let mut game = Game::new(GoBoard::new(9, Ruleset::default()));
+        game.play(Move::Play(2, 2)).unwrap();
+        game.play(Move::Play(4, 4)).unwrap();
+        assert_eq!(&position_commands(&game)[3..], ["play b C7", "play w E5"]);
+
+        game.edit(2, 2, BoardCellOption::None);
+        assert_eq!(&position_commands(&game)[3..], ["play w E5"]);
+    }
+}
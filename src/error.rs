@@ -0,0 +1,100 @@
+//! Errors from reading and writing files.
+
+use std::io;
+
+use crate::sgf::SgfError;
+
+/// A save, record or asset file that could not be used
+#[derive(Debug)]
+pub enum FileError {
+    /// The file could not be read or written
+    Io {
+        /// Path of the file
+        path: String,
+        /// Underlying error
+        source: io::Error
+    },
+    /// The file is not a valid save
+    Save {
+        /// Path of the file
+        path: String,
+        /// Underlying error
+        source: serde_json::Error
+    },
+    /// The save was written by a newer version of go_rs
+    Version {
+        /// Path of the file
+        path: String,
+        /// Format version found in the file
+        version: u32
+    },
+    /// The save is well-formed but describes an impossible game
+    Invalid {
+        /// Path of the file
+        path: String,
+        /// The first problem found
+        reason: String
+    },
+    /// The file is not a valid SGF record
+    Sgf {
+        /// Path of the file
+        path: String,
+        /// Underlying error
+        source: SgfError
+    },
+    /// A sound, font or image could not be loaded
+    Asset {
+        /// Path of the file
+        path: String,
+        /// What went wrong, as reported by the loader
+        message: String
+    }
+}
+
+impl FileError {
+    /// Path of the file the error is about
+    pub fn path(&self) -> &str {
+        match self {
+            FileError::Io { path, .. } |
+            FileError::Save { path, .. } |
+            FileError::Version { path, .. } |
+            FileError::Invalid { path, .. } |
+            FileError::Sgf { path, .. } |
+            FileError::Asset { path, .. } => path
+        }
+    }
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileError::Io { path, source } => write!(f, "Could not access {}: {}", path, source),
+            FileError::Save { path, source } => write!(f, "{} is not a valid save: {}", path, source),
+            FileError::Version { path, version } => write!(f, "{} uses save format {}, newer than this version of go_rs", path, version),
+            FileError::Invalid { path, reason } => write!(f, "{} is damaged: {}", path, reason),
+            FileError::Sgf { path, source } => write!(f, "{} is not a valid SGF record: {}", path, source),
+            FileError::Asset { path, message } => write!(f, "Could not load {}: {}", path, message)
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FileError::Io { source, .. } => Some(source),
+            FileError::Save { source, .. } => Some(source),
+            FileError::Sgf { source, .. } => Some(source),
+            FileError::Version { .. } | FileError::Invalid { .. } | FileError::Asset { .. } => None
+        }
+    }
+}
+
+/// Reads a whole text file
+pub(crate) fn read_file(path: &str) -> Result<String, FileError> {
+    std::fs::read_to_string(path).map_err(|source| FileError::Io { path: path.to_string(), source })
+}
+
+/// Creates or replaces a file
+pub(crate) fn write_file(path: &str, contents: &str) -> Result<(), FileError> {
+    std::fs::write(path, contents).map_err(|source| FileError::Io { path: path.to_string(), source })
+}
@@ -0,0 +1,669 @@
+//! A full game: move tree, passes, resignation, scoring, handicap and clocks.
+
+use serde::{Serialize, Deserialize};
+
+use crate::{BoardCellOption, Cluster, GoBoard, IllegalMove, MoveOutcome};
+use crate::clock::GameClock;
+use crate::error::FileError;
+use crate::save::{load_game, save_game};
+use crate::scoring::{score, Score};
+use crate::tree::{GameTree, Setup};
+
+/// A turn of one player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Move {
+    /// A stone on (x, y)
+    Play(usize, usize),
+    /// Giving up the turn
+    Pass,
+    /// Giving up the game
+    Resign
+}
+
+/// Stage of the game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Phase {
+    /// Players take turns
+    Playing,
+    /// Both players passed and are marking dead stones
+    Scoring,
+    /// The result is decided
+    Finished
+}
+
+/// How a finished game was decided
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum GameResult {
+    /// The loser resigned
+    Resignation {
+        /// Player who did not resign
+        winner: BoardCellOption
+    },
+    /// The loser ran out of time
+    Time {
+        /// Player who still had time
+        winner: BoardCellOption
+    },
+    /// The game was counted
+    Score {
+        /// Player with the higher score; None is a draw (jigo)
+        winner: BoardCellOption,
+        /// Difference between the scores
+        margin: f32
+    }
+}
+
+impl std::fmt::Display for GameResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameResult::Resignation { winner } => write!(f, "{:?} wins by resignation", winner),
+            GameResult::Time { winner } => write!(f, "{:?} wins on time", winner),
+            GameResult::Score { winner: BoardCellOption::None, .. } => write!(f, "Draw"),
+            GameResult::Score { winner, margin } => write!(f, "{:?} wins by {}", winner, margin)
+        }
+    }
+}
+
+/// A played move with everything needed to take it back exactly
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveRecord {
+    /// Player who made the move
+    pub color: BoardCellOption,
+    /// The move itself
+    pub mv: Move,
+    /// Stones it removed
+    pub outcome: MoveOutcome,
+    /// Black's prisoners before the move
+    pub captured_black: usize,
+    /// White's prisoners before the move
+    pub captured_white: usize,
+    passes: usize
+}
+
+/// Record information that is not part of the moves
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GameInfo {
+    /// Name of the black player
+    pub black_name: String,
+    /// Name of the white player
+    pub white_name: String,
+    /// Number of handicap stones
+    pub handicap: usize,
+    /// Result stored in a loaded record, which may differ from how far the tree has been played
+    pub result: Option<GameResult>
+}
+
+/// The current position is always derived by replaying the tree from the root to the current node
+#[derive(Serialize, Deserialize)]
+pub struct Game {
+    /// Empty board the tree is replayed on, carrying size, ruleset and any initial prisoners
+    pub start: GoBoard,
+    /// All moves, setup and comments, with variations
+    pub tree: GameTree,
+    /// Tree node of the current position
+    pub current: usize,
+    /// The current position
+    pub board: GoBoard,
+    /// Players, handicap and recorded result
+    pub info: GameInfo,
+    /// Moves on the path from the root to the current node
+    pub moves: Vec<MoveRecord>,
+    /// Points given to White
+    pub komi: f32,
+    /// Stage of the game at the current position
+    pub phase: Phase,
+    /// Result once the game is finished
+    pub result: Option<GameResult>,
+    /// Score breakdown when the game was counted
+    pub score: Option<Score>,
+    /// Stones marked dead during scoring
+    pub dead: Vec<[usize; 2]>,
+    /// Whether black and white accepted the dead stones marked so far
+    pub agreed: [bool; 2],
+    /// Free handicap stones Black still has to place before White's first move
+    #[serde(default)]
+    pub handicap_to_place: usize,
+    /// Clocks of a timed game
+    #[serde(default)]
+    pub clock: Option<GameClock>,
+    passes: usize
+}
+
+impl Game {
+    /// Stones already on the board become setup stones of the root node
+    pub fn new(board: GoBoard) -> Self {
+        let mut start = GoBoard::new(board.size, board.ruleset);
+        start.captured_black = board.captured_black;
+        start.captured_white = board.captured_white;
+
+        let mut tree = GameTree::default();
+        let root = &mut tree.nodes[0].setup;
+        for y in 0..board.size {
+            for x in 0..board.size {
+                match board.board[y][x] {
+                    BoardCellOption::Black => root.black.push([x, y]),
+                    BoardCellOption::White => root.white.push([x, y]),
+                    BoardCellOption::None => {}
+                }
+            }
+        }
+        if board.to_move == BoardCellOption::White {
+            root.to_move = Some(BoardCellOption::White);
+        }
+
+        let mut game = Game {
+            komi: board.ruleset.default_komi(),
+            start,
+            tree,
+            current: 0,
+            board,
+            info: GameInfo::default(),
+            moves: vec![],
+            phase: Phase::Playing,
+            result: None,
+            score: None,
+            dead: vec![],
+            agreed: [false; 2],
+            handicap_to_place: 0,
+            clock: None,
+            passes: 0
+        };
+        game.goto(0);
+        game
+    }
+
+    /// Reads a save of any version, see [`crate::save`]
+    pub fn load_from_file(path: &str) -> Result<Self, FileError> {
+        load_game(path)
+    }
+
+    /// Writes the whole game, including the tree and clocks, as a versioned JSON save
+    pub fn save_to_file(&self, path: &str) -> Result<(), FileError> {
+        save_game(self, path)
+    }
+
+    /// Rebuilds the position by replaying from the root to the node
+    pub fn goto(&mut self, node: usize) {
+        // Moves entered the tree through the same rules, so replaying them cannot fail
+        let _ = self.replay(node);
+    }
+
+    /// Like goto, but reports the first node whose move the rules reject
+    pub fn replay(&mut self, node: usize) -> Result<(), (usize, IllegalMove)> {
+        self.board = self.start.clone();
+        self.moves.clear();
+        self.phase = Phase::Playing;
+        self.result = None;
+        self.score = None;
+        self.dead.clear();
+        self.agreed = [false; 2];
+        self.passes = 0;
+
+        let mut error = None;
+        for n in self.tree.path(node) {
+            let setup = self.tree.nodes[n].setup.clone();
+            if !setup.is_empty() {
+                self.apply_setup(&setup);
+            }
+            if let Some((color, mv)) = self.tree.nodes[n].mv {
+                self.phase = Phase::Playing;
+                self.board.to_move = color;
+                if let Err(e) = self.apply(mv) {
+                    error.get_or_insert((n, e));
+                }
+            }
+            self.tree.select(n);
+        }
+        self.current = node;
+
+        // Running out of time ends the game at the end of the line it happened on
+        if self.phase == Phase::Playing && self.tree.nodes[node].children.is_empty() {
+            self.check_flag();
+        }
+        error.map_or(Ok(()), Err)
+    }
+
+    /// Runs the clock of the side to move, returning true when this ends the game on time
+    pub fn tick_clock(&mut self, delta: f32) -> bool {
+        if self.phase != Phase::Playing || self.handicap_to_place > 0 {
+            return false;
+        }
+        let to_move = self.board.to_move;
+        let flagged = self.clock.as_mut().is_some_and(|clock| clock.tick(to_move, delta));
+        flagged && self.check_flag()
+    }
+
+    fn check_flag(&mut self) -> bool {
+        let flagged = match &self.clock {
+            Some(clock) if clock.black.flagged => BoardCellOption::Black,
+            Some(clock) if clock.white.flagged => BoardCellOption::White,
+            _ => return false
+        };
+        self.phase = Phase::Finished;
+        self.result = Some(GameResult::Time { winner: flagged.opposite() });
+        true
+    }
+
+    /// Handicap can only be given on an empty board before the first move
+    fn can_set_handicap(&self) -> bool {
+        self.current == 0 && self.board.board.iter().flatten().all(|p| *p == BoardCellOption::None)
+    }
+
+    /// Forgets moves taken back to the start, which were played without the handicap
+    fn clear_variations(&mut self) {
+        self.tree.nodes.truncate(1);
+        self.tree.nodes[0].children.clear();
+        self.tree.nodes[0].selected = 0;
+    }
+
+    /// Puts Black's handicap stones on the given points as root setup, White moves next
+    pub fn set_handicap(&mut self, points: &[[usize; 2]]) -> bool {
+        if !self.can_set_handicap() || points.iter().any(|[x, y]| *x >= self.board.size || *y >= self.board.size) {
+            return false;
+        }
+
+        self.clear_variations();
+        self.info.handicap = points.len();
+        let root = &mut self.tree.nodes[0].setup;
+        root.black = points.to_vec();
+        root.to_move = Some(BoardCellOption::White);
+        self.handicap_to_place = 0;
+        self.goto(0);
+        true
+    }
+
+    /// Black places the stones one by one with place_handicap_stone
+    pub fn start_free_handicap(&mut self, stones: usize) -> bool {
+        if !self.can_set_handicap() || stones < 2 || stones >= self.board.size * self.board.size {
+            return false;
+        }
+
+        self.clear_variations();
+        self.info.handicap = stones;
+        self.handicap_to_place = stones;
+        true
+    }
+
+    /// Places one free handicap stone, handing the turn to White after the last
+    pub fn place_handicap_stone(&mut self, x: usize, y: usize) -> Result<(), IllegalMove> {
+        if self.handicap_to_place == 0 || self.current != 0 {
+            return Err(IllegalMove::NotYourTurn);
+        }
+        if x >= self.board.size || y >= self.board.size {
+            return Err(IllegalMove::OutOfBounds);
+        }
+        if self.board.board[y][x] != BoardCellOption::None {
+            return Err(IllegalMove::Occupied);
+        }
+
+        let root = &mut self.tree.nodes[0].setup;
+        root.black.push([x, y]);
+        self.handicap_to_place -= 1;
+        if self.handicap_to_place == 0 {
+            root.to_move = Some(BoardCellOption::White);
+        }
+        self.goto(0);
+        Ok(())
+    }
+
+    /// Jumps to the position after move n along the selected variations
+    pub fn goto_move(&mut self, n: usize) {
+        let mut node = 0;
+        let mut count = 0;
+        while count < n {
+            match self.tree.selected_child(node) {
+                Some(child) => {
+                    node = child;
+                    if self.tree.nodes[child].mv.is_some() {
+                        count += 1;
+                    }
+                },
+                None => break
+            }
+        }
+        self.goto(node);
+    }
+
+    fn apply_setup(&mut self, setup: &Setup) {
+        for &[x, y] in &setup.empty {
+            self.board.set(x, y, BoardCellOption::None);
+        }
+        for &[x, y] in &setup.black {
+            self.board.set(x, y, BoardCellOption::Black);
+        }
+        for &[x, y] in &setup.white {
+            self.board.set(x, y, BoardCellOption::White);
+        }
+        if let Some(color) = setup.to_move {
+            self.board.to_move = color;
+            self.board.reset_history();
+        }
+        self.passes = 0;
+    }
+
+    /// Comment of the current node
+    pub fn comment(&self) -> &str {
+        self.tree.nodes[self.current].comment.as_str()
+    }
+
+    /// Marks the group at (x, y) dead, or alive again if it already was
+    pub fn toggle_dead(&mut self, x: usize, y: usize) {
+        if self.phase != Phase::Scoring || x >= self.board.size || y >= self.board.size {
+            return;
+        }
+        if self.board.board[y][x] == BoardCellOption::None {
+            return;
+        }
+
+        let c = Cluster::from(&self.board, x, y);
+        if self.dead.contains(&[x, y]) {
+            self.dead.retain(|p| !c.pieces.contains(p));
+        } else {
+            self.dead.extend(c.pieces);
+        }
+        self.agreed = [false; 2];
+    }
+
+    /// Accepts the dead stones for one player; the game is counted once both have accepted
+    pub fn agree(&mut self, color: BoardCellOption) {
+        if self.phase != Phase::Scoring {
+            return;
+        }
+
+        match color {
+            BoardCellOption::Black => self.agreed[0] = true,
+            BoardCellOption::White => self.agreed[1] = true,
+            BoardCellOption::None => {}
+        }
+        if self.agreed == [true; 2] {
+            self.count();
+        }
+    }
+
+    /// Players disagree about the status of some stones and continue the game to settle it
+    pub fn resume(&mut self) {
+        if self.phase != Phase::Scoring {
+            return;
+        }
+
+        self.phase = Phase::Playing;
+        self.dead.clear();
+        self.agreed = [false; 2];
+        self.passes = 0;
+    }
+
+    /// Score of the current position with the stones marked dead so far
+    pub fn tentative_score(&self) -> Score {
+        score(&self.board, self.board.ruleset.scoring(), self.komi, &self.dead)
+    }
+
+    /// Counts the final position with the ruleset's scoring method and ends the game
+    fn count(&mut self) {
+        let s = self.tentative_score();
+        self.phase = Phase::Finished;
+        self.result = Some(GameResult::Score { winner: s.winner(), margin: s.margin() });
+        self.score = Some(s);
+    }
+
+    /// Plays for the given colour even if it is not their turn, as records and GTP controllers may do
+    pub fn play_as(&mut self, color: BoardCellOption, mv: Move) -> Result<MoveOutcome, IllegalMove> {
+        let to_move = self.board.to_move;
+        self.board.to_move = color;
+        let result = self.play(mv);
+        if result.is_err() {
+            self.board.to_move = to_move;
+        }
+        result
+    }
+
+    /// Plays a move below the current node, following an existing variation if it has the same move
+    pub fn play(&mut self, mv: Move) -> Result<MoveOutcome, IllegalMove> {
+        let color = self.board.to_move;
+        let outcome = self.apply(mv)?;
+        self.current = self.tree.add_move(self.current, color, mv);
+        if let Some(clock) = self.clock.as_mut() {
+            clock.on_move(color);
+        }
+        Ok(outcome)
+    }
+
+    /// Goes back to the parent node, taking back its move exactly when it has one
+    pub fn undo(&mut self) -> Option<(BoardCellOption, Move)> {
+        let node = &self.tree.nodes[self.current];
+        let parent = node.parent?;
+        let mv = node.mv;
+
+        // Nodes holding only a comment leave the position unchanged
+        if mv.is_none() && node.setup.is_empty() {
+            self.tree.select(self.current);
+            self.current = parent;
+            return None;
+        }
+        if mv.is_none() || !node.setup.is_empty() {
+            self.goto(parent);
+            return None;
+        }
+
+        let record = self.moves.pop()?;
+        if let Move::Play(x, y) = record.mv {
+            for &[px, py] in &record.outcome.self_captured {
+                self.board.board[py][px] = record.color;
+            }
+            self.board.board[y][x] = BoardCellOption::None;
+            for &[px, py] in &record.outcome.captured {
+                self.board.board[py][px] = record.color.opposite();
+            }
+        }
+        if record.mv != Move::Resign {
+            self.board.history.pop();
+        }
+
+        self.board.captured_black = record.captured_black;
+        self.board.captured_white = record.captured_white;
+        self.board.to_move = record.color;
+        self.passes = record.passes;
+        self.phase = Phase::Playing;
+        self.result = None;
+        self.score = None;
+        self.dead.clear();
+        self.agreed = [false; 2];
+
+        self.tree.select(self.current);
+        self.current = parent;
+        mv
+    }
+
+    /// Goes forward along the variation last visited
+    pub fn redo(&mut self) -> Option<(BoardCellOption, Move)> {
+        let child = self.tree.selected_child(self.current)?;
+        let node = &self.tree.nodes[child];
+
+        match node.mv {
+            Some((color, mv)) if node.setup.is_empty() && self.phase == Phase::Playing => {
+                self.board.to_move = color;
+                self.apply(mv).ok()?;
+                self.current = child;
+                Some((color, mv))
+            },
+            _ => {
+                self.goto(child);
+                None
+            }
+        }
+    }
+
+    /// Switches to the previous (-1) or next (+1) variation at the current move
+    pub fn switch_variation(&mut self, offset: isize) -> bool {
+        match self.tree.sibling(self.current, offset) {
+            Some(sibling) => {
+                self.goto(sibling);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Deletes the current node with all variations below it
+    pub fn delete_branch(&mut self) -> bool {
+        match self.tree.remove(self.current) {
+            Some(parent) => {
+                self.goto(parent);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Setup/edit path: adds the stone change as a setup node below the current one
+    pub fn edit(&mut self, x: usize, y: usize, piece: BoardCellOption) {
+        if x >= self.board.size || y >= self.board.size {
+            return;
+        }
+
+        let mut setup = Setup::default();
+        match piece {
+            BoardCellOption::Black => setup.black.push([x, y]),
+            BoardCellOption::White => setup.white.push([x, y]),
+            BoardCellOption::None => setup.empty.push([x, y])
+        }
+
+        let node = &mut self.tree.nodes[self.current];
+        // Consecutive edits are collected in one setup node
+        if node.mv.is_none() && node.children.is_empty() && self.current != 0 {
+            node.setup.black.retain(|p| *p != [x, y]);
+            node.setup.white.retain(|p| *p != [x, y]);
+            node.setup.empty.retain(|p| *p != [x, y]);
+            node.setup.black.extend(setup.black);
+            node.setup.white.extend(setup.white);
+            node.setup.empty.extend(setup.empty);
+            self.goto(self.current);
+        } else {
+            let child = self.tree.add_setup(self.current, setup);
+            self.goto(child);
+        }
+    }
+
+    /// Applies a move to the position without touching the tree
+    fn apply(&mut self, mv: Move) -> Result<MoveOutcome, IllegalMove> {
+        if self.phase != Phase::Playing {
+            return Err(IllegalMove::GameOver);
+        }
+        if self.handicap_to_place > 0 {
+            return Err(IllegalMove::HandicapPending);
+        }
+
+        let color = self.board.to_move;
+        let captured_black = self.board.captured_black;
+        let captured_white = self.board.captured_white;
+        let passes = self.passes;
+
+        let outcome = match mv {
+            Move::Play(x, y) => {
+                let outcome = self.board.play(color, x, y)?;
+                self.passes = 0;
+                outcome
+            },
+            Move::Pass => {
+                self.board.pass(color)?;
+                self.passes += 1;
+                if self.passes >= 2 {
+                    self.phase = Phase::Scoring;
+                }
+                MoveOutcome::default()
+            },
+            Move::Resign => {
+                self.phase = Phase::Finished;
+                self.result = Some(GameResult::Resignation { winner: color.opposite() });
+                MoveOutcome::default()
+            }
+        };
+
+        self.moves.push(MoveRecord {
+            color,
+            mv,
+            outcome: outcome.clone(),
+            captured_black,
+            captured_white,
+            passes
+        });
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ruleset;
+
+    fn game(size: usize) -> Game {
+        Game::new(GoBoard::new(size, Ruleset::default()))
+    }
+
+    #[test]
+    fn undo_restores_captured_stones() {
+        let mut game = game(5);
+        game.play(Move::Play(1, 0)).unwrap();
+        game.play(Move::Play(0, 0)).unwrap();
+        assert_eq!(game.play(Move::Play(0, 1)).unwrap().captured, vec![[0, 0]]);
+        assert_eq!(game.board.captured_black, 1);
+
+        assert_eq!(game.undo(), Some((BoardCellOption::Black, Move::Play(0, 1))));
+        assert_eq!(game.board.board[0][0], BoardCellOption::White);
+        assert_eq!(game.board.board[1][0], BoardCellOption::None);
+        assert_eq!(game.board.captured_black, 0);
+        assert_eq!(game.board.to_move, BoardCellOption::Black);
+
+        assert_eq!(game.redo(), Some((BoardCellOption::Black, Move::Play(0, 1))));
+        assert_eq!(game.board.board[0][0], BoardCellOption::None);
+        assert_eq!(game.board.captured_black, 1);
+    }
+
+    #[test]
+    fn undo_keeps_the_ko_history() {
+        let mut game = game(5);
+        for mv in [(1, 1), (2, 1), (0, 2), (3, 2), (1, 3), (2, 3), (4, 4), (1, 2)] {
+            game.play(Move::Play(mv.0, mv.1)).unwrap();
+        }
+        game.play(Move::Play(2, 2)).unwrap();
+        assert_eq!(game.play(Move::Play(1, 2)).unwrap_err(), IllegalMove::Ko);
+
+        game.undo();
+        game.redo();
+        assert_eq!(game.play(Move::Play(1, 2)).unwrap_err(), IllegalMove::Ko);
+    }
+
+    #[test]
+    fn handicap_after_taking_back_every_move() {
+        let mut game = game(9);
+        game.play(Move::Play(2, 6)).unwrap();
+        assert!(!game.set_handicap(&[[2, 6], [6, 2]]));
+
+        game.undo();
+        assert!(game.set_handicap(&[[2, 6], [6, 2]]));
+        assert_eq!(game.tree.nodes.len(), 1);
+        assert_eq!(game.board.to_move, BoardCellOption::White);
+        assert!(!game.set_handicap(&[[2, 2], [6, 6]]));
+        assert!(!game.start_free_handicap(2));
+    }
+
+    #[test]
+    fn two_passes_start_scoring() {
+        let mut game = game(5);
+        game.play(Move::Pass).unwrap();
+        assert_eq!(game.phase, Phase::Playing);
+        game.play(Move::Pass).unwrap();
+        assert_eq!(game.phase, Phase::Scoring);
+
+        game.undo();
+        assert_eq!(game.phase, Phase::Playing);
+    }
+
+    #[test]
+    fn resignation_ends_the_game() {
+        let mut game = game(5);
+        game.play(Move::Resign).unwrap();
+        assert_eq!(game.phase, Phase::Finished);
+        assert_eq!(game.result, Some(GameResult::Resignation { winner: BoardCellOption::White }));
+        assert_eq!(game.play(Move::Pass).unwrap_err(), IllegalMove::GameOver);
+    }
+}
@@ -0,0 +1,354 @@
+//! Go Text Protocol front end, so other programs can use the rules engine.
+
+use std::io::{self, BufRead, Write};
+
+use crate::{splitmix64, BoardCellOption, GoBoard, Ruleset};
+use crate::game::{Game, Move};
+use crate::coords::{column_label, format_move, format_point, parse_point, row_label, COLUMNS};
+use crate::handicap::fixed_handicap;
+use crate::scoring::score;
+
+const COMMANDS: &[&str] = &[
+    "protocol_version",
+    "name",
+    "version",
+    "known_command",
+    "list_commands",
+    "quit",
+    "boardsize",
+    "clear_board",
+    "komi",
+    "play",
+    "genmove",
+    "undo",
+    "showboard",
+    "final_score",
+    "fixed_handicap",
+    "place_free_handicap",
+    "set_free_handicap"
+];
+
+/// GTP vertex of a point, such as "D4"
+pub fn format_vertex(x: usize, y: usize, size: usize) -> String {
+    format_point(x, y, size)
+}
+
+/// Returns None for "pass"
+pub fn parse_vertex(vertex: &str, size: usize) -> Result<Option<[usize; 2]>, String> {
+    if vertex.eq_ignore_ascii_case("pass") {
+        return Ok(None);
+    }
+
+    match parse_point(vertex, size) {
+        Some(point) => Ok(Some(point)),
+        None => Err(String::from("invalid vertex"))
+    }
+}
+
+/// Parses "b", "w", "black" or "white"
+pub fn parse_color(color: &str) -> Result<BoardCellOption, String> {
+    match color.to_lowercase().as_str() {
+        "b" | "black" => Ok(BoardCellOption::Black),
+        "w" | "white" => Ok(BoardCellOption::White),
+        _ => Err(String::from("invalid color"))
+    }
+}
+
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self.0.wrapping_add(1);
+        (splitmix64(self.0) % bound as u64) as usize
+    }
+}
+
+/// A point whose neighbours are all stones of the given colour; filling it only hurts
+fn is_own_eye(board: &GoBoard, color: BoardCellOption, x: usize, y: usize) -> bool {
+    board.neighbours(x, y).iter().all(|[nx, ny]| board.board[*ny][*nx] == color)
+}
+
+/// Picks a random legal move that does not fill an own eye, preferring captures, or passes
+fn generate_move(board: &GoBoard, color: BoardCellOption, rng: &mut Rng) -> Move {
+    let mut candidates = vec![];
+    for y in 0..board.size {
+        for x in 0..board.size {
+            if board.board[y][x] == BoardCellOption::None && !is_own_eye(board, color, x, y) {
+                candidates.push([x, y]);
+            }
+        }
+    }
+    for i in (1..candidates.len()).rev() {
+        candidates.swap(i, rng.next(i + 1));
+    }
+
+    let mut fallback = None;
+    for [x, y] in candidates {
+        let mut b = board.clone();
+        b.to_move = color;
+        match b.play(color, x, y) {
+            Ok(outcome) if !outcome.captured.is_empty() => return Move::Play(x, y),
+            Ok(outcome) if outcome.self_captured.is_empty() && fallback.is_none() => fallback = Some(Move::Play(x, y)),
+            _ => {}
+        }
+    }
+    fallback.unwrap_or(Move::Pass)
+}
+
+/// Game state behind a GTP session
+pub struct GtpEngine {
+    game: Game,
+    size: usize,
+    komi: f32,
+    ruleset: Ruleset,
+    rng: Rng
+}
+
+impl GtpEngine {
+    /// A session with an empty board
+    pub fn new(size: usize, ruleset: Ruleset) -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        GtpEngine {
+            game: Game::new(GoBoard::new(size, ruleset)),
+            size,
+            komi: ruleset.default_komi(),
+            ruleset,
+            rng: Rng(seed)
+        }
+    }
+
+    fn clear_board(&mut self) {
+        self.game = Game::new(GoBoard::new(self.size, self.ruleset));
+        self.game.komi = self.komi;
+    }
+
+    /// Returns the response text, or an error message for a failure response
+    pub fn execute(&mut self, command: &str, args: &[&str]) -> Result<String, String> {
+        match command {
+            "protocol_version" => Ok(String::from("2")),
+            "name" => Ok(String::from("go_rs")),
+            "version" => Ok(String::from(env!("CARGO_PKG_VERSION"))),
+            "known_command" => Ok(COMMANDS.contains(args.first().unwrap_or(&"")).to_string()),
+            "list_commands" => Ok(COMMANDS.join("\n")),
+            "quit" => Ok(String::new()),
+            "boardsize" => {
+                match args.first().and_then(|a| a.parse::<usize>().ok()) {
+                    Some(size) if (2..=COLUMNS.len()).contains(&size) => {
+                        self.size = size;
+                        self.clear_board();
+                        Ok(String::new())
+                    },
+                    Some(_) => Err(String::from("unacceptable size")),
+                    None => Err(String::from("syntax error"))
+                }
+            },
+            "clear_board" => {
+                self.clear_board();
+                Ok(String::new())
+            },
+            "komi" => {
+                let komi = args.first().and_then(|a| a.parse::<f32>().ok()).ok_or("syntax error")?;
+                self.komi = komi;
+                self.game.komi = komi;
+                Ok(String::new())
+            },
+            "play" => {
+                if args.len() < 2 {
+                    return Err(String::from("syntax error"));
+                }
+                let color = parse_color(args[0])?;
+                let mv = match parse_vertex(args[1], self.size)? {
+                    Some([x, y]) => Move::Play(x, y),
+                    None => Move::Pass
+                };
+                self.game.resume();
+                self.game.play_as(color, mv).map_err(|_| String::from("illegal move"))?;
+                Ok(String::new())
+            },
+            "genmove" => {
+                let color = parse_color(args.first().ok_or("syntax error")?)?;
+                let mv = generate_move(&self.game.board, color, &mut self.rng);
+                self.game.resume();
+                self.game.play_as(color, mv).map_err(|e| e.to_string())?;
+                Ok(format_move(mv, self.size))
+            },
+            "undo" => {
+                match self.game.undo() {
+                    Some(_) => Ok(String::new()),
+                    None => Err(String::from("cannot undo"))
+                }
+            },
+            "fixed_handicap" | "place_free_handicap" => {
+                let stones = args.first().and_then(|a| a.parse::<usize>().ok()).ok_or("syntax error")?;
+                // Free placement picks the same points as fixed placement
+                let points = fixed_handicap(self.size, stones).ok_or("invalid number of stones")?;
+                if !self.game.set_handicap(&points) {
+                    return Err(String::from("board not empty"));
+                }
+                Ok(points.iter().map(|[x, y]| format_vertex(*x, *y, self.size)).collect::<Vec<_>>().join(" "))
+            },
+            "set_free_handicap" => {
+                let mut points = vec![];
+                for vertex in args {
+                    match parse_vertex(vertex, self.size)? {
+                        Some(p) if !points.contains(&p) => points.push(p),
+                        _ => return Err(String::from("bad vertex list"))
+                    }
+                }
+                if points.len() < 2 {
+                    return Err(String::from("bad vertex list"));
+                }
+                if !self.game.set_handicap(&points) {
+                    return Err(String::from("board not empty"));
+                }
+                Ok(String::new())
+            },
+            "showboard" => Ok(self.showboard()),
+            "final_score" => {
+                let s = score(&self.game.board, self.ruleset.scoring(), self.game.komi, &[]);
+                Ok(match s.winner() {
+                    BoardCellOption::Black => format!("B+{}", s.margin()),
+                    BoardCellOption::White => format!("W+{}", s.margin()),
+                    BoardCellOption::None => String::from("0")
+                })
+            },
+            _ => Err(String::from("unknown command"))
+        }
+    }
+
+    fn showboard(&self) -> String {
+        let board = &self.game.board;
+        let letters = (0..self.size).map(column_label).collect::<Vec<_>>().join(" ");
+
+        let mut out = format!("\n   {}\n", letters);
+        for y in 0..self.size {
+            let row = board.board[y].iter().map(|c| match c {
+                BoardCellOption::Black => "X",
+                BoardCellOption::White => "O",
+                BoardCellOption::None => "."
+            }).collect::<Vec<_>>().join(" ");
+            out += format!("{:>2} {} {}\n", row_label(y, self.size), row, row_label(y, self.size)).as_str();
+        }
+        out += format!("   {}\n", letters).as_str();
+        out += format!("Black captured: {} White captured: {}", board.captured_black, board.captured_white).as_str();
+        out
+    }
+}
+
+/// Answers GTP commands from the input with the given session until "quit" or the end of input
+pub fn run<R: BufRead, W: Write>(mut engine: GtpEngine, input: R, mut output: W) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        // Comments and control characters other than tabs are ignored
+        let line = line.split('#').next().unwrap_or("")
+            .chars()
+            .map(|c| if c == '\t' { ' ' } else { c })
+            .filter(|c| !c.is_control())
+            .collect::<String>();
+
+        let mut words = line.split_whitespace().collect::<Vec<_>>();
+        if words.is_empty() {
+            continue;
+        }
+
+        let id = match words[0].parse::<u32>() {
+            Ok(id) => {
+                words.remove(0);
+                id.to_string()
+            },
+            Err(_) => String::new()
+        };
+        let Some(command) = words.first().copied() else {
+            continue;
+        };
+
+        match engine.execute(command, &words[1..]) {
+            Ok(response) => write!(output, "={} {}\n\n", id, response)?,
+            Err(message) => write!(output, "?{} {}\n\n", id, message)?
+        }
+        output.flush()?;
+
+        if command == "quit" {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(commands: &str) -> String {
+        let mut output = vec![];
+        run(GtpEngine::new(9, Ruleset::Japanese), commands.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn vertices_and_colors() {
+        assert_eq!(format_vertex(3, 15, 19), "D4");
+        assert_eq!(format_vertex(8, 0, 19), "J19");
+        assert_eq!(parse_vertex("d4", 19), Ok(Some([3, 15])));
+        assert_eq!(parse_vertex("PASS", 19), Ok(None));
+        assert!(parse_vertex("I5", 19).is_err());
+        assert!(parse_vertex("T20", 19).is_err());
+        assert_eq!(parse_color("White"), Ok(BoardCellOption::White));
+        assert!(parse_color("red").is_err());
+    }
+
+    #[test]
+    fn responses_carry_ids_and_stop_at_quit() {
+        let output = session("1 protocol_version\n# comment\n\nknown_command play\nfoo\n2 quit\nname\n");
+        assert_eq!(output, "=1 2\n\n= true\n\n? unknown command\n\n=2 \n\n");
+    }
+
+    #[test]
+    fn play_capture_and_undo() {
+        let mut engine = GtpEngine::new(19, Ruleset::Japanese);
+        assert_eq!(engine.execute("boardsize", &["9"]), Ok(String::new()));
+        engine.execute("komi", &["0.5"]).unwrap();
+        for (color, vertex) in [("b", "B9"), ("w", "A9"), ("b", "A8")] {
+            engine.execute("play", &[color, vertex]).unwrap();
+        }
+        assert_eq!(engine.game.board.board[0][0], BoardCellOption::None);
+        assert_eq!(engine.game.board.captured_black, 1);
+        assert_eq!(engine.execute("play", &["w", "A9"]), Err(String::from("illegal move")));
+        assert_eq!(engine.execute("play", &["w", "Z1"]), Err(String::from("invalid vertex")));
+        assert_eq!(engine.execute("final_score", &[]), Ok(String::from("B+79.5")));
+
+        engine.execute("undo", &[]).unwrap();
+        assert_eq!(engine.game.board.board[0][0], BoardCellOption::White);
+        assert_eq!(engine.execute("boardsize", &["30"]), Err(String::from("unacceptable size")));
+    }
+
+    #[test]
+    fn genmove_plays_a_legal_move() {
+        let mut engine = GtpEngine::new(9, Ruleset::Japanese);
+        let vertex = engine.execute("genmove", &["b"]).unwrap();
+        match parse_vertex(vertex.as_str(), 9).unwrap() {
+            Some([x, y]) => assert_eq!(engine.game.board.board[y][x], BoardCellOption::Black),
+            None => assert_eq!(engine.game.moves.len(), 1)
+        }
+        assert_eq!(engine.game.board.to_move, BoardCellOption::White);
+    }
+
+    #[test]
+    fn handicap_needs_an_empty_board() {
+        let mut engine = GtpEngine::new(9, Ruleset::Japanese);
+        assert_eq!(engine.execute("fixed_handicap", &["2"]), Ok(String::from("C3 G7")));
+        assert_eq!(engine.execute("fixed_handicap", &["2"]), Err(String::from("board not empty")));
+
+        engine.execute("clear_board", &[]).unwrap();
+        engine.execute("play", &["b", "E5"]).unwrap();
+        engine.execute("undo", &[]).unwrap();
+        assert_eq!(engine.execute("set_free_handicap", &["A1"]), Err(String::from("bad vertex list")));
+        assert_eq!(engine.execute("set_free_handicap", &["A1", "B2"]), Ok(String::new()));
+        assert_eq!(engine.game.board.to_move, BoardCellOption::White);
+    }
+}
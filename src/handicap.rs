@@ -0,0 +1,100 @@
+//! Standard handicap stone placement.
+
+/// Distance of the corner star points from the edge, counted from 0
+fn edge(size: usize) -> usize {
+    if size >= 13 { 3 } else { 2 }
+}
+
+/// Star points in the order the GTP specification places fixed handicap stones
+fn handicap_points(size: usize) -> Vec<[usize; 2]> {
+    let e = edge(size);
+    let far = size - 1 - e;
+    let mid = size / 2;
+
+    // D4 Q16 D16 Q4 are lower left, upper right, upper left and lower right
+    let mut points = vec![[e, far], [far, e], [e, e], [far, far]];
+    if size % 2 == 1 && size > 7 {
+        points.extend([[e, mid], [far, mid], [mid, far], [mid, e], [mid, mid]]);
+    }
+    points
+}
+
+/// Points marked with a dot on the board: the four corner points from 7x7 up, the center on odd
+/// sizes and the side points on odd boards from 15x15 up
+pub fn star_points(size: usize) -> Vec<[usize; 2]> {
+    let mid = size / 2;
+    if size < 7 {
+        return if size % 2 == 1 { vec![[mid, mid]] } else { vec![] };
+    }
+
+    let e = edge(size);
+    let far = size - 1 - e;
+    let mut points = vec![[e, e], [far, e], [e, far], [far, far]];
+    if size % 2 == 1 {
+        points.push([mid, mid]);
+    }
+    if size % 2 == 1 && size >= 15 {
+        points.extend([[e, mid], [far, mid], [mid, e], [mid, far]]);
+    }
+    points
+}
+
+/// Most fixed handicap stones the board size can take
+pub fn max_handicap(size: usize) -> usize {
+    if size < 7 {
+        0
+    } else {
+        handicap_points(size).len()
+    }
+}
+
+/// Standard placement of 2 to 9 handicap stones, or None if the board cannot take that many
+pub fn fixed_handicap(size: usize, stones: usize) -> Option<Vec<[usize; 2]>> {
+    if stones < 2 || stones > max_handicap(size) {
+        return None;
+    }
+
+    let points = handicap_points(size);
+    Some(match stones {
+        // Odd counts from 5 up take the center point plus the pattern for one stone fewer
+        5 | 7 | 9 => {
+            let mut p = points[..stones - 1].to_vec();
+            p.push([size / 2, size / 2]);
+            p
+        },
+        _ => points[..stones].to_vec()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stones_follow_the_gtp_order() {
+        assert_eq!(fixed_handicap(19, 2), Some(vec![[3, 15], [15, 3]]));
+        assert_eq!(fixed_handicap(19, 5).unwrap().last(), Some(&[9, 9]));
+        assert_eq!(fixed_handicap(19, 9).unwrap().len(), 9);
+        assert_eq!(fixed_handicap(9, 4), Some(vec![[2, 6], [6, 2], [2, 2], [6, 6]]));
+    }
+
+    #[test]
+    fn small_boards_take_fewer_stones() {
+        assert_eq!(max_handicap(19), 9);
+        assert_eq!(max_handicap(8), 4);
+        assert_eq!(max_handicap(7), 4);
+        assert_eq!(max_handicap(5), 0);
+        assert_eq!(fixed_handicap(8, 5), None);
+        assert_eq!(fixed_handicap(19, 1), None);
+        assert_eq!(fixed_handicap(19, 10), None);
+    }
+
+    #[test]
+    fn star_points_by_size() {
+        assert_eq!(star_points(19).len(), 9);
+        assert_eq!(star_points(13).len(), 5);
+        assert_eq!(star_points(8).len(), 4);
+        assert_eq!(star_points(5), vec![[2, 2]]);
+        assert!(star_points(4).is_empty());
+    }
+}
@@ -0,0 +1,31 @@
+#![warn(missing_docs)]
+//! Rules engine behind the go_rs board.
+//!
+//! [`GoBoard`] holds a position and enforces turn order, captures, suicide and ko for a
+//! [`Ruleset`]. [`game::Game`] builds a full game on top of it: a tree of moves with
+//! variations, passes and resignation, dead stone marking, scoring, handicap and clocks.
+//! Games can be read and written as SGF through [`sgf`], driven over the Go Text Protocol
+//! through [`gtp`], and external GTP engines can be played against through [`engine`].
+//!
+//! Every type that makes up a saved game implements serde's `Serialize` and `Deserialize`.
+
+pub mod board;
+pub mod clock;
+pub mod config;
+pub mod coords;
+pub mod engine;
+pub mod error;
+pub mod game;
+pub mod gtp;
+pub mod handicap;
+pub mod paths;
+pub mod save;
+pub mod scoring;
+pub mod session;
+pub mod sgf;
+pub mod theme;
+pub mod tree;
+
+pub use board::{BoardCellOption, Cluster, GoBoard, IllegalMove, KoRule, MoveOutcome, Ruleset};
+pub use error::FileError;
+pub(crate) use board::splitmix64;
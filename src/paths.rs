@@ -0,0 +1,67 @@
+//! Where go_rs keeps files between runs.
+//!
+//! `GO_RS_HOME` overrides both directories, which keeps tests and portable installs self-contained.
+
+use std::env::var_os;
+use std::path::PathBuf;
+
+fn home() -> Option<PathBuf> {
+    var_os("HOME").or_else(|| var_os("USERPROFILE")).map(PathBuf::from)
+}
+
+/// Directory for autosaves, recent files and the session lock
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = var_os("GO_RS_HOME") {
+        return PathBuf::from(dir);
+    }
+
+    let base = if cfg!(windows) {
+        var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|h| h.join("Library").join("Application Support"))
+    } else {
+        var_os("XDG_DATA_HOME").map(PathBuf::from).or_else(|| home().map(|h| h.join(".local").join("share")))
+    };
+    base.unwrap_or_else(|| PathBuf::from(".")).join("go_rs")
+}
+
+/// Directory for the configuration file
+pub fn config_dir() -> PathBuf {
+    if let Some(dir) = var_os("GO_RS_HOME") {
+        return PathBuf::from(dir);
+    }
+
+    let base = if cfg!(windows) {
+        var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|h| h.join("Library").join("Application Support"))
+    } else {
+        var_os("XDG_CONFIG_HOME").map(PathBuf::from).or_else(|| home().map(|h| h.join(".config")))
+    };
+    base.unwrap_or_else(|| PathBuf::from(".")).join("go_rs")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Points `GO_RS_HOME` at an empty folder for this test run; every test touching files in
+    /// the data or config directory goes through here, so they agree on the folder
+    pub(crate) fn home() -> PathBuf {
+        static HOME: std::sync::OnceLock<PathBuf> = std::sync::OnceLock::new();
+        HOME.get_or_init(|| {
+            let home = std::env::temp_dir().join(format!("go_rs_home_{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&home);
+            std::fs::create_dir_all(&home).unwrap();
+            std::env::set_var("GO_RS_HOME", &home);
+            home
+        }).clone()
+    }
+
+    #[test]
+    fn go_rs_home_overrides_both_directories() {
+        let home = home();
+        assert_eq!(data_dir(), home);
+        assert_eq!(config_dir(), home);
+    }
+}
@@ -0,0 +1,332 @@
+//! The versioned `.gs` save format.
+//!
+//! Saves are JSON objects of the form `{"version": 2, "game": {...}}`. Earlier layouts are
+//! migrated when they are loaded:
+//!
+//! - version 0 is a bare [`GoBoard`], as written before games had a move history
+//! - version 1 is a bare [`Game`], as written before saves carried a version
+//!
+//! Every save is validated before it is used, so a damaged file is reported instead of
+//! indexing out of bounds later.
+
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::{BoardCellOption, Cluster, GoBoard};
+use crate::coords::format_point;
+use crate::error::{read_file, write_file, FileError};
+use crate::game::{Game, Move, Phase};
+
+/// Version written by [`save_game`]
+pub const SAVE_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile<G> {
+    version: u32,
+    game: G
+}
+
+/// Writes the game with the current version
+pub fn save_game(game: &Game, path: &str) -> Result<(), FileError> {
+    let save = SaveFile { version: SAVE_VERSION, game };
+    let text = serde_json::to_string(&save).map_err(|source| FileError::Save { path: path.to_string(), source })?;
+    write_file(path, text.as_str())
+}
+
+/// Reads a save of any version, migrating and validating it
+pub fn load_game(path: &str) -> Result<Game, FileError> {
+    let text = read_file(path)?;
+    let value: Value = serde_json::from_str(text.as_str()).map_err(|source| FileError::Save { path: path.to_string(), source })?;
+
+    let invalid = |reason: String| FileError::Invalid { path: path.to_string(), reason };
+    let parse_error = |source| FileError::Save { path: path.to_string(), source };
+
+    let version = match value.get("version") {
+        Some(v) => v.as_u64().ok_or_else(|| invalid(format!("version {} is not a number", v)))? as u32,
+        None if value.get("tree").is_some() => 1,
+        None => 0
+    };
+
+    match version {
+        0 => {
+            let mut board: GoBoard = serde_json::from_value(value).map_err(parse_error)?;
+            validate_board(&board).map_err(invalid)?;
+            // Boards from before ko detection carry no history, later ones may not match a hand-edited position
+            if board.history.last().is_none_or(|(hash, _)| *hash != board.hash()) {
+                board.reset_history();
+            }
+            Ok(Game::new(board))
+        },
+        1 => {
+            let mut game: Game = serde_json::from_value(value).map_err(parse_error)?;
+            validate_game(&mut game).map_err(invalid)?;
+            Ok(game)
+        },
+        SAVE_VERSION => {
+            let save: SaveFile<Game> = serde_json::from_value(value).map_err(parse_error)?;
+            let mut game = save.game;
+            validate_game(&mut game).map_err(invalid)?;
+            Ok(game)
+        },
+        version => Err(FileError::Version { path: path.to_string(), version })
+    }
+}
+
+/// Checks that the board is square, has a player to move and holds no stones without liberties
+pub fn validate_board(board: &GoBoard) -> Result<(), String> {
+    if !(2..=52).contains(&board.size) {
+        return Err(format!("unsupported board size {}", board.size));
+    }
+    if board.board.len() != board.size || board.board.iter().any(|row| row.len() != board.size) {
+        return Err(format!("board does not have {} rows of {} points", board.size, board.size));
+    }
+    if board.to_move == BoardCellOption::None {
+        return Err(String::from("nobody is to move"));
+    }
+
+    for y in 0..board.size {
+        for x in 0..board.size {
+            let c = Cluster::from(board, x, y);
+            if c.color != BoardCellOption::None && !c.has_liberties(board) {
+                return Err(format!("the {:?} stone at {} has no liberties", c.color, format_point(x, y, board.size)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the tree structure and every variation against the rules, then replays to the
+/// current node and compares the result with the stored position and capture counts
+pub fn validate_game(game: &mut Game) -> Result<(), String> {
+    validate_board(&game.start)?;
+    validate_board(&game.board)?;
+    let size = game.start.size;
+    if game.board.size != size {
+        return Err(format!("position is {}x{} but the game is {}x{}", game.board.size, game.board.size, size, size));
+    }
+    if !game.komi.is_finite() {
+        return Err(String::from("komi is not a number"));
+    }
+
+    let nodes = &game.tree.nodes;
+    if nodes.is_empty() || nodes[0].parent.is_some() {
+        return Err(String::from("game tree has no root"));
+    }
+    if game.current >= nodes.len() {
+        return Err(format!("current node {} is not in the tree", game.current));
+    }
+
+    // Every node must be reached exactly once from the root, through children that point back to it
+    let mut seen = vec![false; nodes.len()];
+    let mut stack = vec![0];
+    while let Some(n) = stack.pop() {
+        if seen[n] {
+            return Err(format!("node {} is reached twice", n));
+        }
+        seen[n] = true;
+
+        let node = &nodes[n];
+        for &child in &node.children {
+            if child >= nodes.len() || nodes[child].parent != Some(n) {
+                return Err(format!("node {} has a broken child link", n));
+            }
+            stack.push(child);
+        }
+
+        let in_bounds = |[x, y]: &[usize; 2]| *x < size && *y < size;
+        if let Some((color, mv)) = node.mv {
+            if color == BoardCellOption::None {
+                return Err(format!("node {} has a move without a player", n));
+            }
+            if let Move::Play(x, y) = mv {
+                if !in_bounds(&[x, y]) {
+                    return Err(format!("node {} plays outside the board", n));
+                }
+            }
+        }
+        let setup = &node.setup;
+        if !setup.black.iter().chain(&setup.white).chain(&setup.empty).all(in_bounds) {
+            return Err(format!("node {} sets up stones outside the board", n));
+        }
+    }
+    if let Some(n) = seen.iter().position(|s| !s) {
+        return Err(format!("node {} is not connected to the root", n));
+    }
+
+    // Replaying moves the current node and changes which variation is selected, and starts scoring
+    // over; all of these should survive loading
+    let current = game.current;
+    let (phase, result, agreed) = (game.phase, game.result, game.agreed);
+    let (score, dead) = (game.score.take(), std::mem::take(&mut game.dead));
+    let stored = game.board.clone();
+    let selected = nodes.iter().map(|n| n.selected).collect::<Vec<_>>();
+    let leaves = (0..nodes.len()).filter(|n| nodes[*n].children.is_empty()).collect::<Vec<_>>();
+    for leaf in leaves {
+        if let Err((n, e)) = game.replay(leaf) {
+            return Err(format!("move at node {} is illegal: {}", n, e));
+        }
+    }
+    for (node, selected) in game.tree.nodes.iter_mut().zip(selected) {
+        node.selected = selected;
+    }
+
+    game.goto(current);
+    if game.board.board != stored.board {
+        return Err(String::from("stored position does not match the moves"));
+    }
+    if (game.board.captured_black, game.board.captured_white) != (stored.captured_black, stored.captured_white) {
+        return Err(format!(
+            "stored prisoners (Black {}, White {}) do not match the moves (Black {}, White {})",
+            stored.captured_black,
+            stored.captured_white,
+            game.board.captured_black,
+            game.board.captured_white
+        ));
+    }
+
+    for &[x, y] in &dead {
+        if x >= size || y >= size {
+            return Err(String::from("a dead stone is marked outside the board"));
+        }
+        if game.board.board[y][x] == BoardCellOption::None {
+            return Err(format!("the empty point {} is marked dead", format_point(x, y, size)));
+        }
+    }
+    // Play resumed after both players passed
+    if phase == Phase::Playing && game.phase == Phase::Scoring {
+        game.resume();
+    }
+    game.phase = phase;
+    game.result = result;
+    game.score = score;
+    game.dead = dead;
+    game.agreed = agreed;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ruleset;
+    use crate::game::GameResult;
+
+    fn temp_file(name: &str) -> String {
+        std::env::temp_dir().join(format!("go_rs_{}_{}.gs", name, std::process::id())).to_string_lossy().into_owned()
+    }
+
+    fn reload(game: &Game, name: &str) -> Game {
+        let path = temp_file(name);
+        save_game(game, path.as_str()).unwrap();
+        let loaded = load_game(path.as_str()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        loaded
+    }
+
+    fn write_temp(name: &str, text: &str) -> String {
+        let path = temp_file(name);
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn bare_boards_are_migrated() {
+        let mut board = GoBoard::new(9, Ruleset::Chinese);
+        board.play(BoardCellOption::Black, 2, 2).unwrap();
+        board.play(BoardCellOption::White, 6, 6).unwrap();
+        let mut value = serde_json::to_value(&board).unwrap();
+        // The oldest boards were written before these fields existed
+        for field in ["to_move", "ruleset", "history"] {
+            value.as_object_mut().unwrap().remove(field);
+        }
+
+        let path = write_temp("v0", value.to_string().as_str());
+        let game = load_game(path.as_str()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(game.tree.nodes[0].setup.black, vec![[2, 2]]);
+        assert_eq!(game.tree.nodes[0].setup.white, vec![[6, 6]]);
+        assert_eq!(game.board.ruleset, Ruleset::Japanese);
+        assert_eq!(game.board.to_move, BoardCellOption::Black);
+    }
+
+    #[test]
+    fn unversioned_games_are_migrated() {
+        let mut game = Game::new(GoBoard::new(9, Ruleset::default()));
+        game.play(Move::Play(2, 2)).unwrap();
+        game.play(Move::Play(6, 6)).unwrap();
+        game.undo();
+
+        let path = write_temp("v1", serde_json::to_string(&game).unwrap().as_str());
+        let mut loaded = load_game(path.as_str()).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.current, 1);
+        assert_eq!(loaded.tree.nodes.len(), 3);
+        assert_eq!(loaded.redo(), Some((BoardCellOption::White, Move::Play(6, 6))));
+    }
+
+    #[test]
+    fn damaged_saves_are_rejected() {
+        let mut game = Game::new(GoBoard::new(9, Ruleset::default()));
+        game.play(Move::Play(2, 2)).unwrap();
+        let mut value = serde_json::to_value(SaveFile { version: SAVE_VERSION, game: &game }).unwrap();
+
+        value["version"] = Value::from(SAVE_VERSION + 1);
+        let path = write_temp("future", value.to_string().as_str());
+        assert!(matches!(load_game(path.as_str()), Err(FileError::Version { .. })));
+
+        value["version"] = Value::from(SAVE_VERSION);
+        value["game"]["board"]["board"][2][2] = Value::from("White");
+        std::fs::write(&path, value.to_string()).unwrap();
+        assert!(matches!(load_game(path.as_str()), Err(FileError::Invalid { .. })));
+
+        std::fs::write(&path, "{").unwrap();
+        assert!(matches!(load_game(path.as_str()), Err(FileError::Save { .. })));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn board_errors_name_the_point() {
+        let mut board = GoBoard::new(9, Ruleset::default());
+        board.board[0][0] = BoardCellOption::White;
+        board.board[0][1] = BoardCellOption::Black;
+        board.board[1][0] = BoardCellOption::Black;
+        assert_eq!(validate_board(&board), Err(String::from("the White stone at A9 has no liberties")));
+    }
+
+    #[test]
+    fn counted_game_stays_finished() {
+        let mut game = Game::new(GoBoard::new(5, Ruleset::default()));
+        game.play(Move::Play(2, 2)).unwrap();
+        game.play(Move::Pass).unwrap();
+        game.play(Move::Pass).unwrap();
+        game.agree(BoardCellOption::Black);
+        game.agree(BoardCellOption::White);
+        assert_eq!(game.phase, Phase::Finished);
+
+        let loaded = reload(&game, "counted");
+        assert_eq!(loaded.phase, Phase::Finished);
+        assert!(matches!(loaded.result, Some(GameResult::Score { winner: BoardCellOption::Black, .. })));
+        assert_eq!(loaded.score, game.score);
+    }
+
+    #[test]
+    fn scoring_and_resumed_games_keep_their_phase() {
+        let mut game = Game::new(GoBoard::new(5, Ruleset::default()));
+        game.play(Move::Play(2, 2)).unwrap();
+        game.play(Move::Play(0, 0)).unwrap();
+        game.play(Move::Pass).unwrap();
+        game.play(Move::Pass).unwrap();
+        game.toggle_dead(0, 0);
+        game.agree(BoardCellOption::White);
+
+        let loaded = reload(&game, "scoring");
+        assert_eq!(loaded.phase, Phase::Scoring);
+        assert_eq!(loaded.dead, vec![[0, 0]]);
+        assert_eq!(loaded.agreed, [false, true]);
+
+        game.resume();
+        let mut loaded = reload(&game, "resumed");
+        assert_eq!(loaded.phase, Phase::Playing);
+        loaded.play(Move::Pass).unwrap();
+        assert_eq!(loaded.phase, Phase::Playing);
+    }
+}
@@ -0,0 +1,228 @@
+//! Counting finished positions by territory or area.
+
+use serde::{Serialize, Deserialize};
+
+use crate::{BoardCellOption, GoBoard};
+
+/// How a finished position is counted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScoringMethod {
+    /// Territory plus prisoners (Japanese, Korean)
+    Territory,
+    /// Stones on the board plus territory (Chinese, AGA)
+    Area
+}
+
+/// Counted result of a position
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Score {
+    /// Method the score was counted with
+    pub method: ScoringMethod,
+    /// Komi added to White's score
+    pub komi: f32,
+    /// Empty points surrounded only by Black
+    pub black_territory: usize,
+    /// Empty points surrounded only by White
+    pub white_territory: usize,
+    /// Living black stones on the board
+    pub black_stones: usize,
+    /// Living white stones on the board
+    pub white_stones: usize,
+    /// Prisoners taken by Black, including dead white stones
+    pub black_prisoners: usize,
+    /// Prisoners taken by White, including dead black stones
+    pub white_prisoners: usize,
+    /// Black's total
+    pub black: f32,
+    /// White's total including komi
+    pub white: f32
+}
+
+impl Score {
+    /// Player with the higher total, None for a draw
+    pub fn winner(&self) -> BoardCellOption {
+        if self.black > self.white {
+            BoardCellOption::Black
+        } else if self.white > self.black {
+            BoardCellOption::White
+        } else {
+            BoardCellOption::None
+        }
+    }
+
+    /// Difference between the totals
+    pub fn margin(&self) -> f32 {
+        (self.black - self.white).abs()
+    }
+
+    /// One line explaining how the player's total adds up
+    pub fn breakdown(&self, color: BoardCellOption) -> String {
+        let (territory, stones, prisoners, total) = match color {
+            BoardCellOption::White => (self.white_territory, self.white_stones, self.white_prisoners, self.white),
+            _ => (self.black_territory, self.black_stones, self.black_prisoners, self.black)
+        };
+
+        let mut text = match self.method {
+            ScoringMethod::Territory => format!("{:?}: territory {} + prisoners {}", color, territory, prisoners),
+            ScoringMethod::Area => format!("{:?}: stones {} + territory {}", color, stones, territory)
+        };
+        if color == BoardCellOption::White {
+            text += format!(" + komi {}", self.komi).as_str();
+        }
+        text + format!(" = {}", total).as_str()
+    }
+}
+
+/// Counts the board with the given method and komi
+///
+/// Dead stones are taken off the board first and count as prisoners for the opponent
+pub fn score(board: &GoBoard, method: ScoringMethod, komi: f32, dead: &[[usize; 2]]) -> Score {
+    let mut cleaned = board.clone();
+    for &[x, y] in dead {
+        match cleaned.board[y][x] {
+            BoardCellOption::Black => cleaned.captured_white += 1,
+            BoardCellOption::White => cleaned.captured_black += 1,
+            BoardCellOption::None => {}
+        }
+        cleaned.board[y][x] = BoardCellOption::None;
+    }
+    let board = &cleaned;
+
+    let mut black_territory = 0;
+    let mut white_territory = 0;
+    let mut black_stones = 0;
+    let mut white_stones = 0;
+
+    for (owner, size) in territories(board) {
+        match owner {
+            BoardCellOption::Black => black_territory += size,
+            BoardCellOption::White => white_territory += size,
+            BoardCellOption::None => {}
+        }
+    }
+
+    for row in &board.board {
+        for cell in row {
+            match cell {
+                BoardCellOption::Black => black_stones += 1,
+                BoardCellOption::White => white_stones += 1,
+                BoardCellOption::None => {}
+            }
+        }
+    }
+
+    let (black, white) = match method {
+        ScoringMethod::Territory => (
+            (black_territory + board.captured_black) as f32,
+            (white_territory + board.captured_white) as f32 + komi
+        ),
+        ScoringMethod::Area => (
+            (black_stones + black_territory) as f32,
+            (white_stones + white_territory) as f32 + komi
+        )
+    };
+
+    Score {
+        method,
+        komi,
+        black_territory,
+        white_territory,
+        black_stones,
+        white_stones,
+        black_prisoners: board.captured_black,
+        white_prisoners: board.captured_white,
+        black,
+        white
+    }
+}
+
+/// Flood-fills every empty region and reports who owns it: a colour if only that colour borders it, None otherwise
+fn territories(board: &GoBoard) -> Vec<(BoardCellOption, usize)> {
+    let mut visited = vec![vec![false; board.size]; board.size];
+    let mut regions = vec![];
+
+    for y in 0..board.size {
+        for x in 0..board.size {
+            if visited[y][x] || board.board[y][x] != BoardCellOption::None {
+                continue;
+            }
+
+            let mut size = 0;
+            let mut touches_black = false;
+            let mut touches_white = false;
+            let mut stack = vec![[x, y]];
+            visited[y][x] = true;
+
+            while let Some([px, py]) = stack.pop() {
+                size += 1;
+                for [nx, ny] in board.neighbours(px, py) {
+                    match board.board[ny][nx] {
+                        BoardCellOption::Black => touches_black = true,
+                        BoardCellOption::White => touches_white = true,
+                        BoardCellOption::None => {
+                            if !visited[ny][nx] {
+                                visited[ny][nx] = true;
+                                stack.push([nx, ny]);
+                            }
+                        }
+                    }
+                }
+            }
+
+            let owner = match (touches_black, touches_white) {
+                (true, false) => BoardCellOption::Black,
+                (false, true) => BoardCellOption::White,
+                _ => BoardCellOption::None
+            };
+            regions.push((owner, size));
+        }
+    }
+
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ruleset;
+
+    // Black owns the left two columns and White the right two, with a dead white stone in Black's area
+    fn position() -> GoBoard {
+        let mut board = GoBoard::new(5, Ruleset::Japanese);
+        for y in 0..5 {
+            board.board[y][2] = BoardCellOption::Black;
+            board.board[y][3] = BoardCellOption::White;
+        }
+        board.board[0][0] = BoardCellOption::White;
+        board.captured_black = 1;
+        board
+    }
+
+    #[test]
+    fn territory_counts_prisoners() {
+        let s = score(&position(), ScoringMethod::Territory, 6.5, &[[0, 0]]);
+        assert_eq!((s.black_territory, s.white_territory), (10, 5));
+        assert_eq!(s.black_prisoners, 2);
+        assert_eq!((s.black, s.white), (12., 11.5));
+        assert_eq!(s.winner(), BoardCellOption::Black);
+        assert_eq!(s.margin(), 0.5);
+        assert_eq!(s.breakdown(BoardCellOption::White), "White: territory 5 + prisoners 0 + komi 6.5 = 11.5");
+    }
+
+    #[test]
+    fn area_counts_stones() {
+        let s = score(&position(), ScoringMethod::Area, 7.5, &[[0, 0]]);
+        assert_eq!((s.black, s.white), (15., 17.5));
+        assert_eq!(s.winner(), BoardCellOption::White);
+
+        // Left on the board, the white stone makes the left side neutral
+        let s = score(&position(), ScoringMethod::Area, 0., &[]);
+        assert_eq!((s.black_territory, s.black), (0, 5.));
+    }
+
+    #[test]
+    fn equal_scores_are_a_draw() {
+        let s = score(&GoBoard::new(3, Ruleset::Chinese), ScoringMethod::Area, 0., &[]);
+        assert_eq!(s.winner(), BoardCellOption::None);
+    }
+}
@@ -0,0 +1,126 @@
+//! Save files kept between runs: timestamped autosaves, the recent files list and crash recovery.
+//!
+//! While the program runs, a lock file in the data directory names the session's autosave.
+//! A clean exit removes it, so finding it at startup means the last session crashed.
+
+use std::fs::{create_dir_all, read_dir, read_to_string, remove_file};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Deserialize};
+
+use crate::error::{write_file, FileError};
+use crate::paths::data_dir;
+
+/// Autosaves older than the newest this many are deleted
+pub const KEEP_AUTOSAVES: usize = 10;
+
+/// Length of the recent files list
+pub const RECENT_FILES: usize = 9;
+
+fn lock_path() -> PathBuf {
+    data_dir().join("session.lock")
+}
+
+fn autosave_dir() -> PathBuf {
+    data_dir().join("autosave")
+}
+
+fn to_string(path: PathBuf) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// UTC date and time as "YYYY-MM-DD HH:MM:SS"
+pub fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rest) = (secs / 86400, secs % 86400);
+
+    // Days since 1970-01-01 to a civil date, counting in 400 year eras that start in March
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, rest / 3600, rest / 60 % 60, rest % 60)
+}
+
+/// Autosave file for a session started at the given time
+pub fn autosave_path(started: SystemTime) -> String {
+    let name = format!("autosave-{}.gs", timestamp(started).replace([' ', ':'], "-"));
+    to_string(autosave_dir().join(name))
+}
+
+/// Makes sure the autosave directory exists and deletes all but the newest autosaves
+pub fn prepare_autosaves() -> Result<(), FileError> {
+    let dir = autosave_dir();
+    create_dir_all(&dir).map_err(|source| FileError::Io { path: to_string(dir.clone()), source })?;
+
+    // Names sort by the time they were started
+    let mut saves = read_dir(&dir)
+        .map_err(|source| FileError::Io { path: to_string(dir.clone()), source })?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.file_name().is_some_and(|n| n.to_string_lossy().starts_with("autosave-")))
+        .collect::<Vec<_>>();
+    saves.sort();
+    if saves.len() > KEEP_AUTOSAVES {
+        for old in &saves[..saves.len() - KEEP_AUTOSAVES] {
+            let _ = remove_file(old);
+        }
+    }
+    Ok(())
+}
+
+/// The autosave of a session that did not exit cleanly, if it got far enough to write one
+pub fn crashed_session() -> Option<String> {
+    let autosave = read_to_string(lock_path()).ok()?;
+    let autosave = autosave.trim().to_string();
+    if PathBuf::from(&autosave).is_file() { Some(autosave) } else { None }
+}
+
+/// Marks this session as running, with the autosave to offer if it crashes
+pub fn begin_session(autosave: &str) -> Result<(), FileError> {
+    let dir = data_dir();
+    create_dir_all(&dir).map_err(|source| FileError::Io { path: to_string(dir), source })?;
+    write_file(to_string(lock_path()).as_str(), autosave)
+}
+
+/// Marks this session as cleanly finished
+pub fn end_session() {
+    let _ = remove_file(lock_path());
+}
+
+/// Recently opened or saved files, most recent first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecentFiles {
+    /// Paths of the files
+    pub files: Vec<String>
+}
+
+impl RecentFiles {
+    /// The stored list, or an empty one if there is none yet
+    pub fn load() -> Self {
+        read_to_string(data_dir().join("recent.json"))
+            .ok()
+            .and_then(|text| serde_json::from_str(text.as_str()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Moves the file to the front of the list and stores the list
+    pub fn add(&mut self, path: &str) -> Result<(), FileError> {
+        // Relative paths would point elsewhere when started from another directory
+        let path = std::fs::canonicalize(path).map(to_string).unwrap_or_else(|_| path.to_string());
+        self.files.retain(|f| *f != path);
+        self.files.insert(0, path);
+        self.files.truncate(RECENT_FILES);
+
+        let dir = data_dir();
+        create_dir_all(&dir).map_err(|source| FileError::Io { path: to_string(dir.clone()), source })?;
+        let text = serde_json::to_string_pretty(self).map_err(|source| FileError::Save { path: to_string(dir.join("recent.json")), source })?;
+        write_file(to_string(dir.join("recent.json")).as_str(), text.as_str())
+    }
+}
@@ -0,0 +1,589 @@
+//! Reading and writing SGF (`FF[4]`) game records.
+
+use crate::{BoardCellOption, GoBoard, Ruleset};
+use crate::error::{read_file, write_file, FileError};
+use crate::game::{Game, GameInfo, GameResult, Move};
+use crate::tree::Setup;
+
+/// One SGF node together with the variations that follow it; the first child is the main line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SgfNode {
+    /// Property identifiers with their values, in file order
+    pub properties: Vec<(String, Vec<String>)>,
+    /// Nodes that follow this one
+    pub children: Vec<SgfNode>
+}
+
+impl SgfNode {
+    /// First value of a property
+    pub fn get(&self, id: &str) -> Option<&str> {
+        self.get_all(id).first().map(|v| v.as_str())
+    }
+
+    /// All values of a property
+    pub fn get_all(&self, id: &str) -> &[String] {
+        self.properties.iter()
+            .find(|(k, _)| k == id)
+            .map(|(_, v)| v.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Replaces a property's values; an empty list leaves the node unchanged
+    pub fn set(&mut self, id: &str, values: Vec<String>) {
+        if values.is_empty() {
+            return;
+        }
+        match self.properties.iter_mut().find(|(k, _)| k == id) {
+            Some((_, v)) => *v = values,
+            None => self.properties.push((id.to_string(), values))
+        }
+    }
+}
+
+/// Malformed SGF text or a record that cannot be replayed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SgfError {
+    /// What went wrong
+    pub message: String,
+    /// Byte offset in the text where it went wrong
+    pub position: usize
+}
+
+impl std::fmt::Display for SgfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SGF error at {}: {}", self.position, self.message)
+    }
+}
+
+impl std::error::Error for SgfError {}
+
+fn error<T>(message: &str, position: usize) -> Result<T, SgfError> {
+    Err(SgfError { message: message.to_string(), position })
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.text.len() && self.text[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.text.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), SgfError> {
+        if self.peek() != Some(c) {
+            return error(format!("expected '{}'", c as char).as_str(), self.pos);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// GameTree = "(" Sequence { GameTree } ")"
+    fn game_tree(&mut self) -> Result<SgfNode, SgfError> {
+        self.expect(b'(')?;
+
+        let mut sequence = vec![];
+        while self.peek() == Some(b';') {
+            sequence.push(self.node()?);
+        }
+        if sequence.is_empty() {
+            return error("game tree without nodes", self.pos);
+        }
+
+        let mut variations = vec![];
+        while self.peek() == Some(b'(') {
+            variations.push(self.game_tree()?);
+        }
+        self.expect(b')')?;
+
+        let mut last = sequence.pop().unwrap();
+        last.children = variations;
+        while let Some(mut node) = sequence.pop() {
+            node.children = vec![last];
+            last = node;
+        }
+        Ok(last)
+    }
+
+    fn node(&mut self) -> Result<SgfNode, SgfError> {
+        self.expect(b';')?;
+
+        let mut node = SgfNode::default();
+        while let Some(c) = self.peek() {
+            if !c.is_ascii_uppercase() {
+                break;
+            }
+            let start = self.pos;
+            while self.pos < self.text.len() && self.text[self.pos].is_ascii_alphabetic() {
+                self.pos += 1;
+            }
+            // FF[3] allowed lowercase letters inside identifiers, they carry no meaning
+            let id = String::from_utf8_lossy(&self.text[start..self.pos])
+                .chars()
+                .filter(|c| c.is_ascii_uppercase())
+                .collect::<String>();
+
+            let mut values = vec![];
+            while self.peek() == Some(b'[') {
+                values.push(self.value()?);
+            }
+            if values.is_empty() {
+                return error(format!("property {} without value", id).as_str(), self.pos);
+            }
+            node.properties.push((id, values));
+        }
+        Ok(node)
+    }
+
+    fn value(&mut self) -> Result<String, SgfError> {
+        self.expect(b'[')?;
+
+        let start = self.pos;
+        let mut bytes = vec![];
+        loop {
+            match self.text.get(self.pos) {
+                None => return error("unterminated property value", start),
+                Some(b']') => break,
+                Some(b'\\') => {
+                    self.pos += 1;
+                    match self.text.get(self.pos) {
+                        // Soft line break
+                        Some(b'\n') => {
+                            if self.text.get(self.pos + 1) == Some(&b'\r') {
+                                self.pos += 1;
+                            }
+                        },
+                        Some(b'\r') => {
+                            if self.text.get(self.pos + 1) == Some(&b'\n') {
+                                self.pos += 1;
+                            }
+                        },
+                        Some(c) => bytes.push(*c),
+                        None => return error("unterminated property value", start)
+                    }
+                },
+                Some(c) => bytes.push(*c)
+            }
+            self.pos += 1;
+        }
+        self.pos += 1;
+
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+/// Parses a collection of game trees
+pub fn parse(text: &str) -> Result<Vec<SgfNode>, SgfError> {
+    let mut parser = Parser { text: text.as_bytes(), pos: 0 };
+
+    let mut trees = vec![];
+    while parser.peek() == Some(b'(') {
+        trees.push(parser.game_tree()?);
+    }
+    if parser.peek().is_some() {
+        return error("unexpected data after the last game tree", parser.pos);
+    }
+    if trees.is_empty() {
+        return error("no game tree found", 0);
+    }
+    Ok(trees)
+}
+
+/// Writes a collection of game trees
+pub fn write(trees: &[SgfNode]) -> String {
+    let mut out = String::new();
+    for tree in trees {
+        write_tree(tree, &mut out);
+        out.push('\n');
+    }
+    out
+}
+
+fn write_tree(node: &SgfNode, out: &mut String) {
+    out.push('(');
+    let mut node = node;
+    loop {
+        write_node(node, out);
+        match node.children.len() {
+            0 => break,
+            1 => node = &node.children[0],
+            _ => {
+                for child in &node.children {
+                    out.push('\n');
+                    write_tree(child, out);
+                }
+                break;
+            }
+        }
+    }
+    out.push(')');
+}
+
+fn write_node(node: &SgfNode, out: &mut String) {
+    out.push(';');
+    for (id, values) in &node.properties {
+        out.push_str(id);
+        for v in values {
+            out.push('[');
+            for c in v.chars() {
+                if c == ']' || c == '\\' {
+                    out.push('\\');
+                }
+                out.push(c);
+            }
+            out.push(']');
+        }
+    }
+}
+
+fn point_to_sgf(x: usize, y: usize) -> String {
+    let letter = |i: usize| {
+        if i < 26 {
+            (b'a' + i as u8) as char
+        } else {
+            (b'A' + (i - 26) as u8) as char
+        }
+    };
+    format!("{}{}", letter(x), letter(y))
+}
+
+/// Returns None for a pass ("" or "tt" on boards up to 19x19)
+fn point_from_sgf(value: &str, size: usize) -> Result<Option<[usize; 2]>, SgfError> {
+    let bytes = value.as_bytes();
+    if bytes.is_empty() || (value == "tt" && size <= 19) {
+        return Ok(None);
+    }
+
+    let coord = |c: u8| match c {
+        b'a'..=b'z' => Some((c - b'a') as usize),
+        b'A'..=b'Z' => Some((c - b'A') as usize + 26),
+        _ => None
+    };
+    match (bytes.len(), coord(bytes[0]), bytes.get(1).and_then(|c| coord(*c))) {
+        (2, Some(x), Some(y)) if x < size && y < size => Ok(Some([x, y])),
+        _ => error(format!("invalid point '{}'", value).as_str(), 0)
+    }
+}
+
+/// Expands compressed point lists such as "aa:cc" into single points
+fn points_from_sgf(values: &[String], size: usize) -> Result<Vec<[usize; 2]>, SgfError> {
+    let mut points = vec![];
+    for v in values {
+        if let Some((a, b)) = v.split_once(':') {
+            if let (Some([x1, y1]), Some([x2, y2])) = (point_from_sgf(a, size)?, point_from_sgf(b, size)?) {
+                for y in y1.min(y2)..=y1.max(y2) {
+                    for x in x1.min(x2)..=x1.max(x2) {
+                        points.push([x, y]);
+                    }
+                }
+            }
+        } else if let Some(p) = point_from_sgf(v, size)? {
+            points.push(p);
+        }
+    }
+    Ok(points)
+}
+
+fn result_to_sgf(result: &GameResult) -> String {
+    let letter = |c: &BoardCellOption| if *c == BoardCellOption::White { "W" } else { "B" };
+    match result {
+        GameResult::Resignation { winner } => format!("{}+R", letter(winner)),
+        GameResult::Time { winner } => format!("{}+T", letter(winner)),
+        GameResult::Score { winner: BoardCellOption::None, .. } => String::from("0"),
+        GameResult::Score { winner, margin } => format!("{}+{}", letter(winner), margin)
+    }
+}
+
+fn result_from_sgf(value: &str) -> Option<GameResult> {
+    if value == "0" || value.eq_ignore_ascii_case("draw") || value.eq_ignore_ascii_case("jigo") {
+        return Some(GameResult::Score { winner: BoardCellOption::None, margin: 0. });
+    }
+
+    let (winner, reason) = value.split_once('+')?;
+    let winner = match winner {
+        "B" => BoardCellOption::Black,
+        "W" => BoardCellOption::White,
+        _ => return None
+    };
+    match reason {
+        "R" | "Resign" => Some(GameResult::Resignation { winner }),
+        "T" | "Time" => Some(GameResult::Time { winner }),
+        _ => reason.parse::<f32>().ok().map(|margin| GameResult::Score { winner, margin })
+    }
+}
+
+fn color_id(color: BoardCellOption) -> &'static str {
+    if color == BoardCellOption::White { "W" } else { "B" }
+}
+
+fn node_to_sgf(game: &Game, index: usize) -> SgfNode {
+    let node = &game.tree.nodes[index];
+    let mut sgf = SgfNode::default();
+
+    match node.mv {
+        Some((color, Move::Play(x, y))) => sgf.set(color_id(color), vec![point_to_sgf(x, y)]),
+        Some((color, Move::Pass)) => sgf.set(color_id(color), vec![String::new()]),
+        Some((_, Move::Resign)) | None => {}
+    }
+
+    let points = |points: &[[usize; 2]]| points.iter().map(|[x, y]| point_to_sgf(*x, *y)).collect::<Vec<_>>();
+    sgf.set("AB", points(&node.setup.black));
+    sgf.set("AW", points(&node.setup.white));
+    sgf.set("AE", points(&node.setup.empty));
+    if let Some(color) = node.setup.to_move {
+        sgf.set("PL", vec![color_id(color).to_string()]);
+    }
+    // Resignation is only recorded in RE, so a resign node is folded into its parent
+    let resigned = |c: &usize| matches!(game.tree.nodes[*c].mv, Some((_, Move::Resign)));
+    let comments = std::iter::once(node.comment.as_str())
+        .chain(node.children.iter().filter(|c| resigned(c)).map(|c| game.tree.nodes[*c].comment.as_str()))
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>();
+    if !comments.is_empty() {
+        sgf.set("C", vec![comments.join("\n\n")]);
+    }
+
+    for child in &node.children {
+        if resigned(child) {
+            sgf.children.extend(node_to_sgf(game, *child).children);
+        } else {
+            sgf.children.push(node_to_sgf(game, *child));
+        }
+    }
+    sgf
+}
+
+/// Writes the game with all variations as an `FF[4]` record
+pub fn game_to_sgf(game: &Game) -> String {
+    let mut root = SgfNode::default();
+    root.set("FF", vec![String::from("4")]);
+    root.set("GM", vec![String::from("1")]);
+    root.set("CA", vec![String::from("UTF-8")]);
+    root.set("AP", vec![format!("go_rs:{}", env!("CARGO_PKG_VERSION"))]);
+    root.set("SZ", vec![game.start.size.to_string()]);
+    root.set("KM", vec![game.komi.to_string()]);
+    root.set("RU", vec![game.start.ruleset.name().to_string()]);
+    if !game.info.black_name.is_empty() {
+        root.set("PB", vec![game.info.black_name.clone()]);
+    }
+    if !game.info.white_name.is_empty() {
+        root.set("PW", vec![game.info.white_name.clone()]);
+    }
+    if game.info.handicap > 0 {
+        root.set("HA", vec![game.info.handicap.to_string()]);
+    }
+    if let Some(clock) = &game.clock {
+        root.set("TM", vec![clock.control.main_time().to_string()]);
+        if let Some(overtime) = clock.control.overtime() {
+            root.set("OT", vec![overtime]);
+        }
+    }
+    if let Some(result) = game.result.as_ref().or(game.info.result.as_ref()) {
+        root.set("RE", vec![result_to_sgf(result)]);
+    }
+
+    let tree = node_to_sgf(game, 0);
+    root.properties.extend(tree.properties);
+    root.children = tree.children;
+
+    write(&[root])
+}
+
+fn setup_from_sgf(node: &SgfNode, size: usize) -> Result<Setup, SgfError> {
+    Ok(Setup {
+        black: points_from_sgf(node.get_all("AB"), size)?,
+        white: points_from_sgf(node.get_all("AW"), size)?,
+        empty: points_from_sgf(node.get_all("AE"), size)?,
+        to_move: match node.get("PL") {
+            Some("W") | Some("w") => Some(BoardCellOption::White),
+            Some(_) => Some(BoardCellOption::Black),
+            None => None
+        }
+    })
+}
+
+/// Adds the SGF node below the game's current node and leaves the game on it
+fn load_node(game: &mut Game, node: &SgfNode, size: usize) -> Result<(), SgfError> {
+    let setup = setup_from_sgf(node, size)?;
+    let mv = match (node.get("B"), node.get("W")) {
+        (Some(v), _) => Some((BoardCellOption::Black, v)),
+        (_, Some(v)) => Some((BoardCellOption::White, v)),
+        _ => None
+    };
+
+    if !setup.is_empty() {
+        let child = game.tree.add_setup(game.current, setup);
+        game.goto(child);
+    } else if mv.is_none() {
+        // Comment-only node, the position stays the same
+        game.current = game.tree.add_setup(game.current, setup);
+    }
+    if let Some((color, value)) = mv {
+        let mv = match point_from_sgf(value, size)? {
+            Some([x, y]) => Move::Play(x, y),
+            None => Move::Pass
+        };
+        // Records are authoritative about who moved, even when a side plays twice or play continues after two passes
+        game.resume();
+        if let Err(e) = game.play_as(color, mv) {
+            return error(format!("move {}: {}", game.moves.len() + 1, e).as_str(), 0);
+        }
+    }
+    if let Some(comment) = node.get("C") {
+        game.tree.nodes[game.current].comment = comment.to_string();
+    }
+
+    load_children(game, node, size)
+}
+
+fn load_children(game: &mut Game, node: &SgfNode, size: usize) -> Result<(), SgfError> {
+    let parent = game.current;
+    for child in &node.children {
+        load_node(game, child, size)?;
+        while game.current != parent {
+            game.undo();
+        }
+    }
+    Ok(())
+}
+
+/// Builds a game from the first record in the collection with all its variations, positioned at the end of the main line
+pub fn game_from_sgf(text: &str) -> Result<Game, SgfError> {
+    let trees = parse(text)?;
+    let root = &trees[0];
+
+    if root.get("GM").is_some_and(|gm| gm != "1") {
+        return error("not a game of Go", 0);
+    }
+    let size = match root.get("SZ") {
+        Some(sz) => match sz.trim().parse::<usize>() {
+            Ok(size) if (2..=52).contains(&size) => size,
+            _ => return error(format!("unsupported board size '{}'", sz).as_str(), 0)
+        },
+        None => 19
+    };
+    let ruleset = root.get("RU").and_then(Ruleset::from_name).unwrap_or_default();
+
+    let mut game = Game::new(GoBoard::new(size, ruleset));
+    if let Some(komi) = root.get("KM").and_then(|km| km.trim().parse::<f32>().ok()) {
+        game.komi = komi;
+    }
+    game.info = GameInfo {
+        black_name: root.get("PB").unwrap_or_default().to_string(),
+        white_name: root.get("PW").unwrap_or_default().to_string(),
+        handicap: root.get("HA").and_then(|ha| ha.trim().parse().ok()).unwrap_or(0),
+        result: root.get("RE").and_then(result_from_sgf)
+    };
+
+    let mut setup = setup_from_sgf(root, size)?;
+    // Black's handicap stones are followed by White's first move
+    if setup.to_move.is_none() && !setup.black.is_empty() && setup.white.is_empty() {
+        setup.to_move = Some(BoardCellOption::White);
+    }
+    game.tree.nodes[0].setup = setup;
+    game.tree.nodes[0].comment = root.get("C").unwrap_or_default().to_string();
+    game.goto(0);
+
+    // The tree's root holds no move, so one on the root node is played as the first move
+    if root.get("B").is_some() || root.get("W").is_some() {
+        let mut first = SgfNode::default();
+        first.set("B", root.get_all("B").to_vec());
+        first.set("W", root.get_all("W").to_vec());
+        first.children = root.children.clone();
+        load_node(&mut game, &first, size)?;
+    } else {
+        load_children(&mut game, root, size)?;
+    }
+    game.goto(game.tree.main_line_end(0));
+
+    Ok(game)
+}
+
+/// Reads an SGF file with [`game_from_sgf`]
+pub fn load_file(path: &str) -> Result<Game, FileError> {
+    game_from_sgf(read_file(path)?.as_str()).map_err(|source| FileError::Sgf { path: path.to_string(), source })
+}
+
+/// Writes the game to an SGF file with [`game_to_sgf`]
+pub fn save_file(game: &Game, path: &str) -> Result<(), FileError> {
+    write_file(path, game_to_sgf(game).as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_write_round_trip() {
+        let text = "(;FF[4]SZ[9]C[a \\] bracket];B[cc](;W[dd];B[ee])(;W[gg]C[second]))\n";
+        let trees = parse(text).unwrap();
+        let root = &trees[0];
+        assert_eq!(root.get("SZ"), Some("9"));
+        assert_eq!(root.get("C"), Some("a ] bracket"));
+        assert_eq!(root.children[0].children.len(), 2);
+        assert_eq!(root.children[0].children[1].get("C"), Some("second"));
+
+        assert_eq!(parse(write(&trees).as_str()).unwrap(), trees);
+    }
+
+    #[test]
+    fn malformed_text_is_an_error() {
+        assert!(parse("").is_err());
+        assert!(parse("(;B[aa]").is_err());
+        assert!(parse("(;B[aa]) trailing").is_err());
+        assert!(game_from_sgf("(;GM[2])").is_err());
+        assert!(game_from_sgf("(;SZ[9];B[zz])").is_err());
+    }
+
+    #[test]
+    fn games_keep_variations_setup_and_info() {
+        let text = "(;GM[1]SZ[9]KM[5.5]RU[Chinese]PB[Ann]PW[Ben]AB[cc][gg]C[start];W[ee](;B[ce];W[ec])(;B[ge]C[side]))";
+        let game = game_from_sgf(text).unwrap();
+        assert_eq!(game.komi, 5.5);
+        assert_eq!(game.start.ruleset, Ruleset::Chinese);
+        assert_eq!(game.info.black_name, "Ann");
+        assert_eq!(game.moves.len(), 3);
+        assert_eq!(game.board.board[2][4], BoardCellOption::White);
+
+        let again = game_from_sgf(game_to_sgf(&game).as_str()).unwrap();
+        assert_eq!(again.tree.nodes.len(), game.tree.nodes.len());
+        assert_eq!(again.board.board, game.board.board);
+        assert_eq!(again.comment(), game.comment());
+        assert_eq!(again.tree.nodes[0].comment, "start");
+        assert_eq!(again.tree.nodes[0].setup.black, vec![[2, 2], [6, 6]]);
+        let side = again.tree.nodes.iter().find(|n| n.comment == "side").unwrap();
+        assert_eq!(side.mv, Some((BoardCellOption::Black, Move::Play(6, 4))));
+    }
+
+    #[test]
+    fn resignations_are_folded_into_the_previous_move() {
+        let mut game = Game::new(GoBoard::new(9, Ruleset::Japanese));
+        game.play(Move::Play(2, 2)).unwrap();
+        game.play(Move::Resign).unwrap();
+        game.tree.nodes[game.current].comment = String::from("gave up");
+
+        let text = game_to_sgf(&game);
+        assert!(text.contains("RE[B+R]"));
+        let again = game_from_sgf(text.as_str()).unwrap();
+        assert_eq!(again.tree.nodes.len(), 2);
+        assert_eq!(again.comment(), "gave up");
+        assert_eq!(game_to_sgf(&again), text);
+    }
+
+    #[test]
+    fn moves_on_the_root_node_are_played() {
+        let game = game_from_sgf("(;SZ[9]B[cc];W[gg])").unwrap();
+        assert_eq!(game.moves.len(), 2);
+        assert_eq!(game.board.board[2][2], BoardCellOption::Black);
+        assert_eq!(game.tree.nodes[1].mv, Some((BoardCellOption::Black, Move::Play(2, 2))));
+        assert!(game_from_sgf("(;SZ[9]B[zz])").is_err());
+    }
+}
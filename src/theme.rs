@@ -0,0 +1,221 @@
+//! Board and stone themes.
+//!
+//! A theme is a JSON file describing the board, grid lines, coordinate labels and stones. Every
+//! part is optional and falls back to the built-in green theme, so a file can be as small as
+//! `{"board": {"color": [200, 160, 90, 255]}}`. Image and font paths are relative to the file.
+//!
+//! Themes are found by name: first the built-ins in [`BUILTIN_THEMES`], then files in the
+//! `themes` folder of the config directory, and finally any other path to a theme file.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+use crate::error::{read_file, FileError};
+use crate::paths::config_dir;
+
+/// Names of the themes that need no files
+pub const BUILTIN_THEMES: [&str; 4] = ["green", "kaya", "dark", "high-contrast"];
+
+/// Board surface
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BoardStyle {
+    /// Colour as `[r, g, b, a]`, drawn under the image if there is one
+    pub color: [u8; 4],
+    /// Picture stretched over the board
+    pub image: Option<String>,
+    /// Colour of wood grain stripes drawn over the board, for boards without an image
+    pub grain: Option<[u8; 4]>
+}
+
+/// Grid lines and star points
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LineStyle {
+    /// Colour as `[r, g, b, a]`
+    pub color: [u8; 4],
+    /// Width as a fraction of the distance between lines
+    pub width: f32
+}
+
+/// Coordinates and other text drawn on the board
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LabelStyle {
+    /// Colour as `[r, g, b, a]`
+    pub color: [u8; 4],
+    /// TrueType font, or `None` for the game's own font
+    pub font: Option<String>
+}
+
+/// Look of one player's stones
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StoneStyle {
+    /// Colour as `[r, g, b, a]`
+    pub color: [u8; 4],
+    /// Colour toward the upper left of the stone, blended with `color` as a gradient
+    pub highlight: Option<[u8; 4]>,
+    /// Ring around the stone, for stones close to the board colour
+    pub outline: Option<[u8; 4]>,
+    /// Picture drawn instead of a circle
+    pub image: Option<String>
+}
+
+/// Everything needed to draw the board
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Name shown when switching themes
+    pub name: String,
+    /// Board surface
+    pub board: BoardStyle,
+    /// Grid lines and star points
+    pub lines: LineStyle,
+    /// Coordinates and text
+    pub labels: LabelStyle,
+    /// Black stones
+    pub black: StoneStyle,
+    /// White stones
+    pub white: StoneStyle,
+    /// Colour of the shadow under each stone, or `None` for flat stones
+    pub shadow: Option<[u8; 4]>
+}
+
+impl Default for BoardStyle {
+    fn default() -> Self {
+        BoardStyle { color: [75, 107, 88, 255], image: None, grain: None }
+    }
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        LineStyle { color: [255, 255, 255, 255], width: 0.05 }
+    }
+}
+
+impl Default for LabelStyle {
+    fn default() -> Self {
+        LabelStyle { color: [255, 255, 255, 255], font: None }
+    }
+}
+
+impl Default for StoneStyle {
+    fn default() -> Self {
+        StoneStyle { color: [0, 0, 0, 255], highlight: None, outline: None, image: None }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: String::from("green"),
+            board: BoardStyle::default(),
+            lines: LineStyle::default(),
+            labels: LabelStyle::default(),
+            black: StoneStyle::default(),
+            white: StoneStyle { color: [255, 255, 255, 255], ..StoneStyle::default() },
+            shadow: None
+        }
+    }
+}
+
+fn themes_dir() -> PathBuf {
+    config_dir().join("themes")
+}
+
+impl Theme {
+    /// One of the [`BUILTIN_THEMES`]
+    pub fn builtin(name: &str) -> Option<Self> {
+        let stone = |color, highlight, outline| StoneStyle { color, highlight, outline, image: None };
+
+        match name {
+            "green" => Some(Theme::default()),
+            "kaya" => Some(Theme {
+                name: String::from("kaya"),
+                board: BoardStyle { color: [219, 176, 102, 255], image: None, grain: Some([160, 110, 50, 40]) },
+                lines: LineStyle { color: [40, 28, 16, 255], width: 0.035 },
+                labels: LabelStyle { color: [40, 28, 16, 255], font: None },
+                black: stone([18, 18, 20, 255], Some([95, 95, 100, 255]), None),
+                white: stone([228, 226, 218, 255], Some([255, 255, 255, 255]), None),
+                shadow: Some([0, 0, 0, 90])
+            }),
+            "dark" => Some(Theme {
+                name: String::from("dark"),
+                board: BoardStyle { color: [32, 33, 38, 255], image: None, grain: None },
+                lines: LineStyle { color: [105, 108, 120, 255], width: 0.04 },
+                labels: LabelStyle { color: [170, 172, 185, 255], font: None },
+                black: stone([8, 8, 10, 255], Some([55, 55, 62, 255]), Some([140, 142, 155, 255])),
+                white: stone([200, 202, 210, 255], Some([245, 245, 250, 255]), None),
+                shadow: Some([0, 0, 0, 140])
+            }),
+            "high-contrast" => Some(Theme {
+                name: String::from("high-contrast"),
+                board: BoardStyle { color: [255, 222, 0, 255], image: None, grain: None },
+                lines: LineStyle { color: [0, 0, 0, 255], width: 0.07 },
+                labels: LabelStyle { color: [0, 0, 0, 255], font: None },
+                black: stone([0, 0, 0, 255], None, None),
+                white: stone([255, 255, 255, 255], None, Some([0, 0, 0, 255])),
+                shadow: None
+            }),
+            _ => None
+        }
+    }
+
+    /// Reads a theme file, resolving image and font paths against its folder
+    pub fn load(path: &str) -> Result<Self, FileError> {
+        let text = read_file(path)?;
+        let mut theme: Theme = serde_json::from_str(text.as_str())
+            .map_err(|e| FileError::Invalid { path: path.to_string(), reason: e.to_string() })?;
+
+        if theme.lines.width <= 0.0 || theme.lines.width > 0.5 {
+            return Err(FileError::Invalid { path: path.to_string(), reason: format!("line width {} is not between 0 and 0.5", theme.lines.width) });
+        }
+
+        let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+        let resolve = |file: &mut Option<String>| {
+            if let Some(f) = file {
+                *f = dir.join(&*f).to_string_lossy().into_owned();
+            }
+        };
+        resolve(&mut theme.board.image);
+        resolve(&mut theme.labels.font);
+        resolve(&mut theme.black.image);
+        resolve(&mut theme.white.image);
+
+        if theme.name.is_empty() || theme.name == Theme::default().name {
+            theme.name = Path::new(path).file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        }
+        Ok(theme)
+    }
+
+    /// A built-in theme, a file in the themes folder named `<name>.json`, or a theme file at that path
+    pub fn find(name: &str) -> Result<Self, FileError> {
+        if let Some(theme) = Theme::builtin(name) {
+            return Ok(theme);
+        }
+
+        let installed = themes_dir().join(format!("{}.json", name));
+        if installed.exists() {
+            return Theme::load(installed.to_string_lossy().as_ref());
+        }
+        Theme::load(name)
+    }
+
+    /// Names of the built-in themes followed by those in the themes folder, for switching through them
+    pub fn available() -> Vec<String> {
+        let mut installed = std::fs::read_dir(themes_dir())
+            .map(|entries| entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+                .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().into_owned()))
+                .filter(|name| Theme::builtin(name).is_none())
+                .collect::<Vec<_>>())
+            .unwrap_or_default();
+        installed.sort();
+
+        BUILTIN_THEMES.iter().map(|n| n.to_string()).chain(installed).collect()
+    }
+}
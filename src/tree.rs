@@ -0,0 +1,234 @@
+//! The game tree of moves, setup stones and comments with variations.
+
+use serde::{Serialize, Deserialize};
+
+use crate::BoardCellOption;
+use crate::game::Move;
+
+/// Stones added or removed without being played, like handicap stones or edits (SGF AB/AW/AE/PL)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Setup {
+    /// Points that get a black stone
+    pub black: Vec<[usize; 2]>,
+    /// Points that get a white stone
+    pub white: Vec<[usize; 2]>,
+    /// Points that are cleared
+    pub empty: Vec<[usize; 2]>,
+    /// Player to move afterwards, if it changes
+    pub to_move: Option<BoardCellOption>
+}
+
+impl Setup {
+    /// Whether the setup changes nothing
+    pub fn is_empty(&self) -> bool {
+        self.black.is_empty() && self.white.is_empty() && self.empty.is_empty() && self.to_move.is_none()
+    }
+}
+
+/// A position in the game tree, reached by a move, setup or both
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Node {
+    /// Move played to reach the node, with the player who made it
+    pub mv: Option<(BoardCellOption, Move)>,
+    /// Stones changed at this node
+    pub setup: Setup,
+    /// Free text comment
+    pub comment: String,
+    /// Index of the parent node, None for the root
+    pub parent: Option<usize>,
+    /// Indices of the variations that follow, the main line first
+    pub children: Vec<usize>,
+    /// Index into children of the variation last visited, followed by redo
+    pub selected: usize
+}
+
+/// All nodes live in one vector and refer to each other by index; `nodes[0]` is the root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameTree {
+    /// Every node of the tree
+    pub nodes: Vec<Node>
+}
+
+impl Default for GameTree {
+    fn default() -> Self {
+        GameTree { nodes: vec![Node::default()] }
+    }
+}
+
+impl GameTree {
+    /// The variation redo would follow from the node
+    pub fn selected_child(&self, node: usize) -> Option<usize> {
+        let n = &self.nodes[node];
+        n.children.get(n.selected).or(n.children.first()).copied()
+    }
+
+    /// Adds a move below the node, reusing an existing variation with the same move
+    pub fn add_move(&mut self, parent: usize, color: BoardCellOption, mv: Move) -> usize {
+        let existing = self.nodes[parent].children.iter()
+            .copied()
+            .find(|c| self.nodes[*c].mv == Some((color, mv)) && self.nodes[*c].setup.is_empty());
+        if let Some(child) = existing {
+            self.select(child);
+            return child;
+        }
+
+        self.add_node(parent, Node { mv: Some((color, mv)), ..Default::default() })
+    }
+
+    /// Adds a setup node below the node
+    pub fn add_setup(&mut self, parent: usize, setup: Setup) -> usize {
+        self.add_node(parent, Node { setup, ..Default::default() })
+    }
+
+    fn add_node(&mut self, parent: usize, mut node: Node) -> usize {
+        let index = self.nodes.len();
+        node.parent = Some(parent);
+        self.nodes.push(node);
+        self.nodes[parent].children.push(index);
+        self.select(index);
+        index
+    }
+
+    /// Makes the node the selected variation of its parent
+    pub fn select(&mut self, node: usize) {
+        if let Some(parent) = self.nodes[node].parent {
+            if let Some(i) = self.nodes[parent].children.iter().position(|c| *c == node) {
+                self.nodes[parent].selected = i;
+            }
+        }
+    }
+
+    /// Node indices from the root down to the given node
+    pub fn path(&self, node: usize) -> Vec<usize> {
+        let mut path = vec![node];
+        let mut n = node;
+        while let Some(parent) = self.nodes[n].parent {
+            path.push(parent);
+            n = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Follows the first variation down to a leaf
+    pub fn main_line_end(&self, node: usize) -> usize {
+        let mut n = node;
+        while let Some(child) = self.nodes[n].children.first() {
+            n = *child;
+        }
+        n
+    }
+
+    /// Follows the selected variations down to a leaf
+    pub fn line_end(&self, node: usize) -> usize {
+        let mut n = node;
+        while let Some(child) = self.selected_child(n) {
+            n = child;
+        }
+        n
+    }
+
+    /// The node's siblings including itself, and its position among them
+    pub fn variations(&self, node: usize) -> (usize, usize) {
+        match self.nodes[node].parent {
+            Some(parent) => {
+                let children = &self.nodes[parent].children;
+                (children.iter().position(|c| *c == node).unwrap_or(0), children.len())
+            },
+            None => (0, 1)
+        }
+    }
+
+    /// The variation offset places before or after the node among its siblings
+    pub fn sibling(&self, node: usize, offset: isize) -> Option<usize> {
+        let parent = self.nodes[node].parent?;
+        let (index, count) = self.variations(node);
+        let index = index.checked_add_signed(offset).filter(|i| *i < count)?;
+        Some(self.nodes[parent].children[index])
+    }
+
+    /// Removes the node and everything below it, returning the parent's new index
+    pub fn remove(&mut self, node: usize) -> Option<usize> {
+        let parent = self.nodes[node].parent?;
+
+        let mut removed = vec![false; self.nodes.len()];
+        let mut stack = vec![node];
+        while let Some(n) = stack.pop() {
+            removed[n] = true;
+            stack.extend(self.nodes[n].children.iter().copied());
+        }
+
+        let mut new_index = vec![0; self.nodes.len()];
+        let mut next = 0;
+        for (i, r) in removed.iter().enumerate() {
+            if !r {
+                new_index[i] = next;
+                next += 1;
+            }
+        }
+
+        self.nodes[parent].children.retain(|c| *c != node);
+        let p = &mut self.nodes[parent];
+        p.selected = p.selected.min(p.children.len().saturating_sub(1));
+
+        let nodes = std::mem::take(&mut self.nodes);
+        self.nodes = nodes.into_iter()
+            .enumerate()
+            .filter(|(i, _)| !removed[*i])
+            .map(|(_, mut n)| {
+                n.parent = n.parent.map(|p| new_index[p]);
+                n.children = n.children.iter().map(|c| new_index[*c]).collect();
+                n
+            })
+            .collect();
+
+        Some(new_index[parent])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(x: usize, y: usize) -> Move {
+        Move::Play(x, y)
+    }
+
+    #[test]
+    fn variations_branch_and_select() {
+        let mut tree = GameTree::default();
+        let a = tree.add_move(0, BoardCellOption::Black, play(3, 3));
+        let b = tree.add_move(a, BoardCellOption::White, play(4, 4));
+        let c = tree.add_move(a, BoardCellOption::White, play(5, 5));
+
+        assert_eq!(tree.path(c), vec![0, a, c]);
+        assert_eq!(tree.variations(c), (1, 2));
+        assert_eq!(tree.selected_child(a), Some(c));
+        assert_eq!(tree.sibling(c, -1), Some(b));
+        assert_eq!(tree.sibling(c, 1), None);
+        assert_eq!(tree.main_line_end(0), b);
+        assert_eq!(tree.line_end(0), c);
+
+        // Playing an existing move follows its variation
+        assert_eq!(tree.add_move(a, BoardCellOption::White, play(4, 4)), b);
+        assert_eq!(tree.selected_child(a), Some(b));
+        assert_eq!(tree.nodes.len(), 4);
+    }
+
+    #[test]
+    fn removing_a_branch_renumbers_the_rest() {
+        let mut tree = GameTree::default();
+        let a = tree.add_move(0, BoardCellOption::Black, play(3, 3));
+        let b = tree.add_move(a, BoardCellOption::White, play(4, 4));
+        tree.add_move(b, BoardCellOption::Black, play(2, 2));
+        let c = tree.add_move(a, BoardCellOption::White, play(5, 5));
+        let d = tree.add_move(c, BoardCellOption::Black, play(6, 6));
+
+        assert_eq!(tree.remove(b), Some(a));
+        assert_eq!(tree.nodes.len(), 4);
+        assert_eq!(tree.nodes[a].children, vec![c - 2]);
+        assert_eq!(tree.path(d - 2), vec![0, a, c - 2, d - 2]);
+        assert_eq!(tree.nodes[d - 2].mv, Some((BoardCellOption::Black, play(6, 6))));
+        assert_eq!(tree.remove(0), None);
+    }
+}
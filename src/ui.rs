@@ -0,0 +1,1560 @@
+use macroquad::prelude::*;
+
+use std::time::SystemTime;
+
+use go_rs::{handicap, session, sgf, BoardCellOption, FileError, GoBoard, IllegalMove};
+use go_rs::clock::GameClock;
+use go_rs::config::{config_path, Action, Config, Coordinates};
+use go_rs::coords::{column_label, format_move, row_label};
+use go_rs::theme::Theme;
+use go_rs::engine::{EngineError, EnginePlayer};
+use go_rs::game::{Game, Move, Phase};
+use go_rs::session::RecentFiles;
+
+use crate::audio::{Effect, Effects, Music};
+use crate::cli::Options;
+
+const DIGIT_KEYS: [KeyCode; 10] = [
+    KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+    KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9
+];
+
+const LETTER_KEYS: [KeyCode; 26] = [
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G,
+    KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N,
+    KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U,
+    KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z
+];
+
+fn rgba([r, g, b, a]: [u8; 4]) -> Color {
+    Color::from_rgba(r, g, b, a)
+}
+
+// Decoded with the image crate first, since macroquad panics on files it cannot read
+fn load_texture_file(path: &str) -> Result<Texture2D, FileError> {
+    let bytes = std::fs::read(path).map_err(|source| FileError::Io { path: path.to_string(), source })?;
+    let image = image::load_from_memory(&bytes)
+        .map_err(|e| FileError::Asset { path: path.to_string(), message: e.to_string() })?
+        .to_rgba8();
+    if image.width() > u16::MAX as u32 || image.height() > u16::MAX as u32 {
+        return Err(FileError::Asset { path: path.to_string(), message: String::from("image is too large") });
+    }
+    Ok(Texture2D::from_rgba8(image.width() as u16, image.height() as u16, image.as_raw()))
+}
+
+fn load_font_file(path: &str) -> Result<Font, FileError> {
+    let bytes = std::fs::read(path).map_err(|source| FileError::Io { path: path.to_string(), source })?;
+    load_ttf_font_from_bytes(&bytes).map_err(|e| FileError::Asset { path: path.to_string(), message: e.to_string() })
+}
+
+// A theme with its images and font loaded
+struct LoadedTheme {
+    theme: Theme,
+    board_image: Option<Texture2D>,
+    black_image: Option<Texture2D>,
+    white_image: Option<Texture2D>,
+    font: Option<Font>
+}
+
+impl LoadedTheme {
+    // Parts that fail to load are left out and drawn from the theme's colours
+    fn new(theme: Theme) -> (Self, Vec<FileError>) {
+        let mut errors = vec![];
+        let mut texture = |path: &Option<String>| match path.as_deref().map(load_texture_file) {
+            Some(Ok(texture)) => Some(texture),
+            Some(Err(e)) => {
+                errors.push(e);
+                None
+            },
+            None => None
+        };
+        let board_image = texture(&theme.board.image);
+        let black_image = texture(&theme.black.image);
+        let white_image = texture(&theme.white.image);
+
+        let font = match theme.labels.font.as_deref().map(load_font_file) {
+            Some(Ok(font)) => Some(font),
+            Some(Err(e)) => {
+                errors.push(e);
+                None
+            },
+            None => None
+        };
+
+        (LoadedTheme { theme, board_image, black_image, white_image, font }, errors)
+    }
+
+    fn line_color(&self) -> Color {
+        rgba(self.theme.lines.color)
+    }
+
+    fn text_color(&self) -> Color {
+        rgba(self.theme.labels.color)
+    }
+
+    fn stone_color(&self, color: BoardCellOption) -> Color {
+        match color {
+            BoardCellOption::White => rgba(self.theme.white.color),
+            _ => rgba(self.theme.black.color)
+        }
+    }
+
+    fn draw_board(&self) {
+        clear_background(rgba(self.theme.board.color));
+        if let Some(texture) = self.board_image {
+            draw_texture_ex(texture, 0., 0., WHITE, DrawTextureParams { 
+                dest_size: Some(vec2(screen_width(), screen_height())), 
+                ..Default::default() 
+            });
+        } else if let Some(grain) = self.theme.board.grain {
+            // Stripes of uneven width and spacing suggest wood grain
+            let mut x = 0.;
+            let mut i = 0;
+            while x < screen_width() {
+                let width = screen_width() * (0.003 + 0.001 * (i * 37 % 11) as f32);
+                draw_rectangle(x, 0., width, screen_height(), rgba(grain));
+                x += width + screen_width() * (0.008 + 0.003 * (i * 53 % 7) as f32);
+                i += 1;
+            }
+        }
+    }
+
+    // Shadow, then a picture or a circle with its gradient and outline
+    fn draw_stone(&self, center: Vec2, radius: f32, color: BoardCellOption, alpha: f32) {
+        let (style, image) = match color {
+            BoardCellOption::Black => (&self.theme.black, self.black_image),
+            BoardCellOption::White => (&self.theme.white, self.white_image),
+            BoardCellOption::None => return
+        };
+        let faded = |c: Color| Color { a: c.a * alpha, ..c };
+
+        if let Some(shadow) = self.theme.shadow {
+            draw_circle(center.x + radius * 0.1, center.y + radius * 0.15, radius, faded(rgba(shadow)));
+        }
+        if let Some(texture) = image {
+            draw_texture_ex(texture, center.x - radius, center.y - radius, faded(WHITE), DrawTextureParams { 
+                dest_size: Some(vec2(radius * 2., radius * 2.)), 
+                ..Default::default() 
+            });
+            return;
+        }
+
+        let base = rgba(style.color);
+        draw_circle(center.x, center.y, radius, faded(base));
+        if let Some(highlight) = style.highlight {
+            // Smaller circles toward the upper left blend into the highlight
+            let highlight = rgba(highlight);
+            let steps = 8;
+            for i in 1..=steps {
+                let t = i as f32 / steps as f32;
+                let mixed = Color::new(
+                    base.r + (highlight.r - base.r) * t,
+                    base.g + (highlight.g - base.g) * t,
+                    base.b + (highlight.b - base.b) * t,
+                    base.a
+                );
+                draw_circle(center.x - radius * 0.3 * t, center.y - radius * 0.3 * t, radius * (1. - 0.75 * t), faded(mixed));
+            }
+        }
+        if let Some(outline) = style.outline {
+            draw_circle_lines(center.x, center.y, radius - radius * 0.04, radius * 0.08, faded(rgba(outline)));
+        }
+    }
+}
+
+impl Drop for LoadedTheme {
+    fn drop(&mut self) {
+        for texture in [self.board_image, self.black_image, self.white_image].into_iter().flatten() {
+            texture.delete();
+        }
+    }
+}
+
+// Key names as written in the config file: letters, digits, F1 to F12 and macroquad's KeyCode names
+fn key_code(name: &str) -> Option<KeyCode> {
+    let name = name.to_lowercase();
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return match c {
+            'a'..='z' => Some(LETTER_KEYS[c as usize - 'a' as usize]),
+            '0'..='9' => Some(DIGIT_KEYS[c as usize - '0' as usize]),
+            '-' => Some(KeyCode::Minus),
+            '=' => Some(KeyCode::Equal),
+            ',' => Some(KeyCode::Comma),
+            '.' => Some(KeyCode::Period),
+            '/' => Some(KeyCode::Slash),
+            '[' => Some(KeyCode::LeftBracket),
+            ']' => Some(KeyCode::RightBracket),
+            _ => None
+        };
+    }
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<usize>().ok()) {
+        return [
+            KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6,
+            KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12
+        ].get(n.wrapping_sub(1)).copied();
+    }
+
+    Some(match name.as_str() {
+        "space" => KeyCode::Space,
+        "enter" | "return" => KeyCode::Enter,
+        "escape" | "esc" => KeyCode::Escape,
+        "backspace" => KeyCode::Backspace,
+        "tab" => KeyCode::Tab,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" | "ins" => KeyCode::Insert,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "minus" => KeyCode::Minus,
+        "equal" | "plus" => KeyCode::Equal,
+        "comma" => KeyCode::Comma,
+        "period" => KeyCode::Period,
+        "slash" => KeyCode::Slash,
+        "backslash" => KeyCode::Backslash,
+        "semicolon" => KeyCode::Semicolon,
+        "apostrophe" => KeyCode::Apostrophe,
+        "leftbracket" => KeyCode::LeftBracket,
+        "rightbracket" => KeyCode::RightBracket,
+        "graveaccent" | "backquote" => KeyCode::GraveAccent,
+        "kpadd" => KeyCode::KpAdd,
+        "kpsubtract" => KeyCode::KpSubtract,
+        "kpmultiply" => KeyCode::KpMultiply,
+        "kpdivide" => KeyCode::KpDivide,
+        "kpenter" => KeyCode::KpEnter,
+        _ => return None
+    })
+}
+
+// A key and the modifiers held with it; "Ctrl+S" does not fire for S alone or Ctrl+Shift+S
+#[derive(Clone, Copy)]
+struct Shortcut {
+    ctrl: bool,
+    shift: bool,
+    key: KeyCode
+}
+
+impl Shortcut {
+    fn parse(spec: &str) -> Option<Self> {
+        let parts = spec.split('+').map(|p| p.trim()).collect::<Vec<_>>();
+        let (key, modifiers) = parts.split_last()?;
+        let mut shortcut = Shortcut { ctrl: false, shift: false, key: key_code(key)? };
+        for modifier in modifiers {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => shortcut.ctrl = true,
+                "shift" => shortcut.shift = true,
+                _ => return None
+            }
+        }
+        Some(shortcut)
+    }
+
+    fn pressed(&self) -> bool {
+        let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        ctrl == self.ctrl && shift == self.shift && is_key_pressed(self.key)
+    }
+}
+
+fn parse_shortcuts(spec: &str) -> Option<Vec<Shortcut>> {
+    spec.split(',').filter(|s| !s.trim().is_empty()).map(Shortcut::parse).collect()
+}
+
+struct Keys {
+    bindings: Vec<(Action, Shortcut)>
+}
+
+impl Keys {
+    // Actions with keys that cannot be read keep their default keys, and the problem is returned
+    fn new(config: &Config) -> (Self, Vec<FileError>) {
+        let mut bindings = vec![];
+        let mut errors = vec![];
+        for action in Action::ALL {
+            let shortcuts = parse_shortcuts(config.keys(action)).unwrap_or_else(|| {
+                errors.push(FileError::Invalid {
+                    path: config_path().to_string_lossy().into_owned(),
+                    reason: format!("unknown keys '{}' for {:?}", config.keys(action), action)
+                });
+                parse_shortcuts(action.default_keys()).unwrap_or_default()
+            });
+            bindings.extend(shortcuts.into_iter().map(|s| (action, s)));
+        }
+        (Keys { bindings }, errors)
+    }
+
+    fn pressed(&self, action: Action) -> bool {
+        self.bindings.iter().any(|(a, s)| *a == action && s.pressed())
+    }
+}
+
+struct Review {
+    autoplay: bool,
+    // Seconds between moves during autoplay
+    speed: f32,
+    timer: f32,
+    // Move number typed so far for a jump
+    jump: String
+}
+
+impl Default for Review {
+    fn default() -> Self {
+        Review { 
+            autoplay: false, 
+            speed: 1.0, 
+            timer: 0.0, 
+            jump: String::new() 
+        }
+    }
+}
+
+// Dialogs that take over the keyboard until they are closed
+enum Prompt {
+    SaveAs(String),
+    Recent,
+    // Autosave left behind by a session that crashed
+    Resume(String)
+}
+
+struct GoBoardUi {
+    size: f32,
+    game: Game,
+    theme: LoadedTheme,
+    status: String,
+    engine: Option<EnginePlayer>,
+    review: Option<Review>,
+    // File and asset problems, shown at the top until the timer runs out
+    error: String,
+    error_timer: f32,
+    prompt: Option<Prompt>,
+    // File the game was last opened from or saved to
+    file: Option<String>,
+    recent: RecentFiles,
+    autosave: Option<String>,
+    moves_since_autosave: usize,
+    config: Config,
+    // False when the config file could not be loaded or backed up, so it is never overwritten
+    config_writable: bool,
+    keys: Keys,
+    effects: Effects,
+    music: Music,
+    audio_settings: bool,
+    // Set by the next track key, and handled by the main loop since loading a track is async
+    next_track: bool,
+    // Seconds left to show a volume just changed, and whether it was the effects volume
+    volume_timer: f32,
+    effects_changed: bool
+}
+
+impl GoBoardUi {
+    // A new game with the configured size, rules and komi
+    fn new(config: Config) -> Self {
+        let mut game = Game::new(GoBoard::new(config.size, config.ruleset));
+        game.komi = config.komi();
+        let (keys, errors) = Keys::new(&config);
+
+        let mut go_game = GoBoardUi {
+            size: 30.,
+            game, 
+            theme: LoadedTheme::new(Theme::default()).0,
+            status: String::new(),
+            engine: None,
+            review: None,
+            error: String::new(),
+            error_timer: 0.,
+            prompt: None,
+            file: None,
+            recent: RecentFiles::load(),
+            autosave: None,
+            moves_since_autosave: 0,
+            config,
+            config_writable: true,
+            keys,
+            effects: Effects::default(),
+            music: Music::default(),
+            audio_settings: false,
+            next_track: false,
+            volume_timer: 0.,
+            effects_changed: false
+        };
+        for e in &errors {
+            go_game.show_error(e);
+        }
+        let theme = go_game.config.theme.clone();
+        go_game.set_theme(theme.as_str());
+        go_game
+    }
+
+    // Falls back to the current theme if the named one cannot be found
+    fn set_theme(&mut self, name: &str) -> bool {
+        match Theme::find(name) {
+            Ok(theme) => {
+                let (theme, errors) = LoadedTheme::new(theme);
+                self.theme = theme;
+                for e in &errors {
+                    self.show_error(e);
+                }
+                true
+            },
+            Err(e) => {
+                self.show_error(&e);
+                false
+            }
+        }
+    }
+
+    // Built-in themes, then those in the themes folder, remembered in the config
+    fn next_theme(&mut self) {
+        let themes = Theme::available();
+        let next = match themes.iter().position(|t| *t == self.config.theme) {
+            Some(i) => themes[(i + 1) % themes.len()].clone(),
+            None => themes[0].clone()
+        };
+        if self.set_theme(next.as_str()) {
+            self.status = format!("Theme {}", self.theme.theme.name);
+            self.config.theme = next;
+            self.save_config();
+        }
+    }
+
+    fn play_effect(&self, effect: Effect) {
+        if self.config.sounds && !self.config.muted {
+            self.effects.play(effect, self.config.effects_volume);
+        }
+    }
+
+    // Volume steps, mute, shuffle and the audio settings panel; changes are stored right away
+    fn update_audio(&mut self) {
+        let volumes = [
+            (Action::MusicLouder, false, 0.1),
+            (Action::MusicQuieter, false, -0.1),
+            (Action::EffectsLouder, true, 0.1),
+            (Action::EffectsQuieter, true, -0.1)
+        ];
+        let mut changed = false;
+        for (action, effects, step) in volumes {
+            if !self.keys.pressed(action) {
+                continue;
+            }
+            let volume = if effects { &mut self.config.effects_volume } else { &mut self.config.music_volume };
+            *volume = ((*volume + step) * 10.).round().clamp(0., 10.) / 10.;
+            self.volume_timer = 2.;
+            self.effects_changed = effects;
+            changed = true;
+            if effects {
+                self.play_effect(Effect::Place);
+            }
+        }
+
+        if self.keys.pressed(Action::Mute) {
+            self.config.muted = !self.config.muted;
+            self.status = String::from(if self.config.muted { "Sound muted" } else { "Sound on" });
+            changed = true;
+        }
+        if self.keys.pressed(Action::Shuffle) {
+            self.config.shuffle = !self.config.shuffle;
+            self.status = String::from(if self.config.shuffle { "Shuffle on" } else { "Shuffle off" });
+            changed = true;
+        }
+        if self.keys.pressed(Action::NextTrack) {
+            self.next_track = true;
+        }
+        if self.keys.pressed(Action::AudioSettings) {
+            self.audio_settings = !self.audio_settings;
+        }
+
+        if changed {
+            self.save_config();
+        }
+    }
+
+    fn save_config(&mut self) {
+        if !self.config_writable {
+            return;
+        }
+        if let Err(e) = self.config.save() {
+            self.show_error(&e);
+        }
+    }
+
+    // .sgf files are written as SGF, everything else in the native format
+    fn save(&mut self, path: &str) {
+        let result = if path.to_lowercase().ends_with(".sgf") {
+            sgf::save_file(&self.game, path)
+        } else {
+            self.game.save_to_file(path)
+        };
+        match result.and_then(|_| self.recent.add(path)) {
+            Ok(_) => {
+                self.status = format!("Saved to {}", path);
+                self.file = Some(path.to_string());
+            },
+            Err(e) => self.show_error(&e)
+        }
+    }
+
+    fn open(&mut self, path: &str) {
+        if self.engine.as_ref().is_some_and(|e| e.is_thinking()) {
+            self.status = String::from("Wait for the engine to move before opening a game");
+            return;
+        }
+
+        let loaded = if path.to_lowercase().ends_with(".sgf") {
+            sgf::load_file(path)
+        } else {
+            Game::load_from_file(path)
+        };
+        match loaded {
+            Ok(game) => {
+                self.game = game;
+                if let Some(review) = &mut self.review {
+                    *review = Review::default();
+                }
+                if let Some(engine) = &mut self.engine {
+                    engine.sync(&self.game);
+                }
+                self.status = format!("Opened {}", path);
+                self.file = Some(path.to_string());
+                self.moves_since_autosave = 0;
+                if let Err(e) = self.recent.add(path) {
+                    self.show_error(&e);
+                }
+            },
+            Err(e) => self.show_error(&e)
+        }
+    }
+
+    fn autosave(&mut self) {
+        self.moves_since_autosave += 1;
+        if self.config.autosave_moves == 0 || self.moves_since_autosave < self.config.autosave_moves {
+            return;
+        }
+        self.moves_since_autosave = 0;
+
+        if let Some(path) = &self.autosave {
+            if let Err(e) = self.game.save_to_file(path.as_str()) {
+                self.show_error(&e);
+            }
+        }
+    }
+
+    fn update_prompt(&mut self) {
+        let Some(prompt) = &mut self.prompt else {
+            return;
+        };
+
+        if is_key_pressed(KeyCode::Escape) {
+            self.prompt = None;
+            return;
+        }
+
+        match prompt {
+            Prompt::SaveAs(name) => {
+                if is_key_pressed(KeyCode::Backspace) {
+                    name.pop();
+                }
+                // Characters as the keyboard layout produces them, without Backspace, Enter and the like
+                while let Some(c) = get_char_pressed() {
+                    if !c.is_control() {
+                        name.push(c);
+                    }
+                }
+                if is_key_pressed(KeyCode::Enter) && !name.trim().is_empty() {
+                    let path = name.trim().to_string();
+                    self.prompt = None;
+                    self.save(path.as_str());
+                }
+            },
+            Prompt::Recent => {
+                let choice = DIGIT_KEYS.iter().skip(1).position(|k| is_key_pressed(*k));
+                if let Some(path) = choice.and_then(|i| self.recent.files.get(i)).cloned() {
+                    self.prompt = None;
+                    self.open(path.as_str());
+                }
+            },
+            Prompt::Resume(path) => {
+                if is_key_pressed(KeyCode::Y) || is_key_pressed(KeyCode::Enter) {
+                    let path = path.clone();
+                    self.prompt = None;
+                    self.open(path.as_str());
+                } else if is_key_pressed(KeyCode::N) {
+                    self.prompt = None;
+                }
+            }
+        }
+    }
+
+    fn show_error(&mut self, e: &FileError) {
+        if self.error_timer > 0. && !self.error.is_empty() {
+            self.error += "; ";
+        } else {
+            self.error.clear();
+        }
+        self.error += e.to_string().as_str();
+        self.error_timer = 8.;
+    }
+
+    // The grid point nearest the mouse, or None when it is more than half a cell outside the grid
+    fn point_under_cursor(&self) -> Option<[usize; 2]> {
+        let size = self.game.board.size;
+        let extent = self.size * (size - 1) as f32;
+        let (mouse_x, mouse_y) = mouse_position();
+        let x = ((mouse_x - (screen_width() - extent) * 0.5) / self.size).round();
+        let y = ((mouse_y - (screen_height() - extent) * 0.5) / self.size).round();
+
+        let on_board = |c: f32| c >= 0. && c < size as f32;
+        (on_board(x) && on_board(y)).then_some([x as usize, y as usize])
+    }
+
+    // Tried on a copy of the board, so occupied points, ko and suicide all count
+    fn can_play_at(&self, x: usize, y: usize) -> bool {
+        if x >= self.game.board.size || y >= self.game.board.size || self.game.board.board[y][x] != BoardCellOption::None {
+            return false;
+        }
+        if self.game.handicap_to_place > 0 {
+            return true;
+        }
+        self.game.board.clone().play(self.game.board.to_move, x, y).is_ok()
+    }
+
+    fn is_human_turn(&self) -> bool {
+        self.engine.as_ref().is_none_or(|e| e.color != self.game.board.to_move)
+    }
+
+    fn draw(&self, font: &Font) {
+        let font = self.theme.font.as_ref().unwrap_or(font);
+
+        let board_width = self.size * (self.game.board.size.wrapping_sub(1)) as f32;
+        let board_height = self.size * (self.game.board.size.wrapping_sub(1)) as f32;
+
+        let start = Vec2::new(
+            screen_width() * 0.5 - board_width * 0.5,
+            screen_height() * 0.5 - board_height * 0.5,
+        );
+
+        self.theme.draw_board();
+        self.draw_coordinates(font, start, board_width, board_height);
+        for i in 0..self.game.board.size {
+            draw_line(
+                start.x,
+                start.y + self.size * i as f32, 
+                start.x + board_width,
+                start.y + self.size * i as f32, 
+                self.size * self.theme.theme.lines.width, 
+                self.theme.line_color()
+            );
+
+            draw_line(
+                start.x + self.size * i as f32,
+                start.y, 
+                start.x + self.size * i as f32,
+                start.y + board_height, 
+                self.size * self.theme.theme.lines.width, 
+                self.theme.line_color()
+            );
+        }
+
+        for [x, y] in handicap::star_points(self.game.board.size) {
+            draw_circle(
+                start.x + self.size * x as f32, 
+                start.y + self.size * y as f32, 
+                self.size * (0.06 + self.theme.theme.lines.width),
+                self.theme.line_color()
+            );
+        }
+
+        for y in 0..self.game.board.board.len() {
+            for x in 0..self.game.board.board[y].len() {
+                let alpha = if self.game.dead.contains(&[x, y]) { 0.35 } else { 1.0 };
+                self.theme.draw_stone(
+                    Vec2::new(start.x + self.size * x as f32, start.y + self.size * y as f32), 
+                    self.size * 0.5, 
+                    self.game.board.board[y][x], 
+                    alpha
+                );
+            }   
+        }
+
+        // A ring in the opposite colour on the stone played last
+        if let Some(Move::Play(x, y)) = self.game.moves.last().map(|record| record.mv) {
+            let color = match self.game.board.board[y][x] {
+                BoardCellOption::None => self.theme.line_color(),
+                stone => self.theme.stone_color(stone.opposite())
+            };
+            draw_circle_lines(
+                start.x + self.size * x as f32, 
+                start.y + self.size * y as f32, 
+                self.size * 0.25,
+                self.size * 0.08,
+                color
+            );
+        }
+
+        if let Some([x, y]) = self.point_under_cursor() {
+            let center = Vec2::new(start.x + self.size * x as f32, start.y + self.size * y as f32);
+            let editing = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+
+            if self.review.is_none() && self.game.phase == Phase::Playing && !editing {
+                // The stone a click would place, left out where the move is illegal
+                if self.is_human_turn() && self.can_play_at(x, y) {
+                    let color = if self.game.handicap_to_place > 0 { BoardCellOption::Black } else { self.game.board.to_move };
+                    self.theme.draw_stone(center, self.size * 0.5, color, 0.45);
+                }
+            } else {
+                draw_circle_lines(
+                    center.x,
+                    center.y,
+                    self.size * 0.5,
+                    5.0,
+                    Color::from_rgba(255, 20, 40, 50)
+                );
+            }
+        }
+
+        let status = if let Some(review) = &self.review {
+            let last = match self.last_move() {
+                Some(last) => format!(" ({})", last),
+                None => String::new()
+            };
+            format!(
+                "Review: move {}{}{} {}{}s/move",
+                self.game.moves.len(),
+                last,
+                if review.jump.is_empty() { String::new() } else { format!(", go to {}", review.jump) },
+                if review.autoplay { "autoplay " } else { "" },
+                review.speed
+            )
+        } else if self.game.phase == Phase::Scoring {
+            let score = self.game.tentative_score();
+            format!(
+                "Click dead groups, B/W to accept, Esc to resume. Black {} White {}{}{}",
+                score.black,
+                score.white,
+                if self.game.agreed[0] { " (Black accepted)" } else { "" },
+                if self.game.agreed[1] { " (White accepted)" } else { "" }
+            )
+        } else if self.engine.as_ref().is_some_and(|e| e.is_thinking()) {
+            format!("White captured: {} Black captured: {} Engine is thinking...", self.game.board.captured_white, self.game.board.captured_black)
+        } else {
+            let last = match self.last_move() {
+                Some(last) => format!(", last {}", last),
+                None => String::new()
+            };
+            format!("White captured: {} Black captured: {} {:?} to move{}", self.game.board.captured_white, self.game.board.captured_black, self.game.board.to_move, last)
+        };
+        // Messages such as "Saved to ..." show whatever the game is doing
+        let status = format!("{} {}", status, self.status);
+
+        draw_text_ex(
+            status.as_str(), 
+            start.x, 
+            start.y + board_height + self.size * 1.9, 
+            TextParams { 
+                font: *font, 
+                font_size: ((self.size * 0.8) as u16).min((screen_width() / 25.) as u16),
+                color: self.theme.text_color(),
+                ..Default::default()
+            }
+        );
+
+        let comment_size = ((self.size * 0.6) as u16).min((screen_width() / 40.) as u16);
+        for (i, line) in self.game.comment().lines().take(3).enumerate() {
+            draw_text_ex(
+                line,
+                comment_size as f32 * 0.5,
+                comment_size as f32 * (1.2 + 1.2 * i as f32),
+                TextParams { 
+                    font: *font, 
+                    font_size: comment_size,
+                    color: self.theme.text_color(),
+                    ..Default::default()
+                }
+            );
+        }
+
+        self.draw_clocks(font, start, board_width, board_height);
+        self.draw_audio(font);
+        self.draw_banner(font);
+        self.draw_prompt(font);
+        self.draw_error(font);
+    }
+
+    // Letters count columns from the left skipping I, numbers count rows from the bottom
+    fn draw_coordinates(&self, font: &Font, start: Vec2, board_width: f32, board_height: f32) {
+        let all_sides = match self.config.coordinates {
+            Coordinates::Hidden => return,
+            Coordinates::TwoSides => false,
+            Coordinates::AllSides => true
+        };
+
+        let size = self.game.board.size;
+        let font_size = (self.size * 0.8) as u16;
+        let label = |text: &str, x: f32, y: f32| {
+            let width = measure_text(text, Some(*font), font_size, 1.0).width;
+            draw_text_ex(
+                text,
+                x - width * 0.5,
+                y,
+                TextParams { 
+                    font: *font,
+                    font_size,
+                    color: self.theme.text_color(),
+                    ..Default::default()
+                }
+            );
+        };
+
+        for i in 0..size {
+            let column = column_label(i);
+            let row = row_label(i, size);
+            let x = start.x + self.size * i as f32;
+            let y = start.y + self.size * i as f32 + self.size * 0.25;
+
+            label(column.as_str(), x, start.y - self.size * 0.7);
+            label(row.as_str(), start.x - self.size * 0.9, y);
+            if all_sides {
+                label(column.as_str(), x, start.y + board_height + self.size * 1.1);
+                label(row.as_str(), start.x + board_width + self.size * 0.9, y);
+            }
+        }
+    }
+
+    // "Black D4" for the move that led to the current position
+    fn last_move(&self) -> Option<String> {
+        self.game.moves.last().map(|record| format!("{:?} {}", record.color, format_move(record.mv, self.game.board.size)))
+    }
+
+    fn draw_prompt(&self, font: &Font) {
+        let lines = match &self.prompt {
+            None => return,
+            Some(Prompt::SaveAs(name)) => vec![
+                String::from("Save as (.sgf for SGF), Enter to save, Esc to cancel:"),
+                format!("{}_", name)
+            ],
+            Some(Prompt::Recent) if self.recent.files.is_empty() => vec![
+                String::from("No recent files. Esc to close")
+            ],
+            Some(Prompt::Recent) => std::iter::once(String::from("Open a recent file with 1-9, Esc to close:"))
+                .chain(self.recent.files.iter().enumerate().map(|(i, f)| format!("{}  {}", i + 1, f)))
+                .collect(),
+            Some(Prompt::Resume(path)) => vec![
+                String::from("The last session did not exit cleanly."),
+                format!("Resume {}? Y/N", path)
+            ]
+        };
+
+        let font_size = ((self.size * 0.6) as u16).min((screen_width() / 40.) as u16);
+        let line_height = font_size as f32 * 1.4;
+        let top = screen_height() * 0.5 - line_height * lines.len() as f32 * 0.5;
+        draw_rectangle(
+            0.,
+            top - line_height,
+            screen_width(),
+            line_height * (lines.len() as f32 + 1.),
+            Color::from_rgba(0, 0, 0, 210)
+        );
+        for (i, line) in lines.iter().enumerate() {
+            draw_text_ex(
+                line.as_str(),
+                font_size as f32,
+                top + line_height * i as f32,
+                TextParams { 
+                    font: *font, 
+                    font_size,
+                    color: WHITE,
+                    ..Default::default()
+                }
+            );
+        }
+    }
+
+    // The audio settings panel, or just the volume being changed while it is closed
+    fn draw_audio(&self, font: &Font) {
+        let font_size = ((self.size * 0.6) as u16).min((screen_width() / 40.) as u16);
+        let line_height = font_size as f32 * 1.5;
+
+        if !self.audio_settings {
+            if self.volume_timer <= 0. {
+                return;
+            }
+            let text = match (self.config.muted, self.effects_changed) {
+                (true, _) => String::from("Muted"),
+                (false, true) => format!("Effects {:.0}%", self.config.effects_volume * 100.),
+                (false, false) => format!("Music {:.0}%", self.config.music_volume * 100.)
+            };
+            let width = measure_text(text.as_str(), Some(*font), font_size, 1.0).width;
+            draw_text_ex(text.as_str(), screen_width() - width - line_height, screen_height() - line_height, TextParams { 
+                font: *font, 
+                font_size,
+                color: self.theme.text_color(),
+                ..Default::default()
+            });
+            return;
+        }
+
+        let key = |action: Action| self.config.keys(action).to_string();
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let track = match self.music.playing() {
+            Some((name, n)) => format!("{} ({}/{})", name, n, self.music.track_count()),
+            None => String::from("none")
+        };
+        let rows = [
+            (String::from("Music"), Some(self.config.music_volume), format!("{} / {}", key(Action::MusicQuieter), key(Action::MusicLouder))),
+            (String::from("Effects"), Some(self.config.effects_volume), format!("{} / {}", key(Action::EffectsQuieter), key(Action::EffectsLouder))),
+            (format!("Sound {}", if self.config.muted { "muted" } else { "on" }), None, key(Action::Mute)),
+            (format!("Track {}", track), None, key(Action::NextTrack)),
+            (format!("Shuffle {}", on_off(self.config.shuffle)), None, key(Action::Shuffle))
+        ];
+
+        let width = (screen_width() * 0.6).max(font_size as f32 * 24.);
+        let left = screen_width() * 0.5 - width * 0.5;
+        let top = screen_height() * 0.5 - line_height * (rows.len() as f32 + 1.) * 0.5;
+        draw_rectangle(left, top - line_height, width, line_height * (rows.len() as f32 + 2.), Color::from_rgba(0, 0, 0, 210));
+
+        let text = |text: &str, x: f32, y: f32, color: Color| {
+            draw_text_ex(text, x, y, TextParams { font: *font, font_size, color, ..Default::default() });
+        };
+        text(format!("Audio settings, {} to close", key(Action::AudioSettings)).as_str(), left + line_height, top, WHITE);
+
+        let bar_left = left + width * 0.3;
+        let bar_width = width * 0.3;
+        for (i, (label, volume, keys)) in rows.iter().enumerate() {
+            let y = top + line_height * (i as f32 + 1.2);
+            text(label.as_str(), left + line_height, y, WHITE);
+            if let Some(volume) = volume {
+                let bar_top = y - font_size as f32 * 0.6;
+                draw_rectangle_lines(bar_left, bar_top, bar_width, font_size as f32 * 0.6, 2., GRAY);
+                draw_rectangle(bar_left, bar_top, bar_width * volume, font_size as f32 * 0.6, if self.config.muted { GRAY } else { WHITE });
+            }
+            text(keys.as_str(), left + width * 0.65, y, LIGHTGRAY);
+        }
+    }
+
+    fn draw_error(&self, font: &Font) {
+        if self.error_timer <= 0. {
+            return;
+        }
+
+        let font_size = ((self.size * 0.6) as u16).min((screen_width() / 40.) as u16);
+        let dimensions = measure_text(self.error.as_str(), Some(*font), font_size, 1.0);
+        draw_rectangle(0., 0., screen_width(), dimensions.height * 2.5, Color::from_rgba(0, 0, 0, 180));
+        draw_text_ex(
+            self.error.as_str(),
+            (screen_width() * 0.5 - dimensions.width * 0.5).max(font_size as f32 * 0.5),
+            dimensions.height * 1.25 + dimensions.offset_y * 0.5,
+            TextParams { 
+                font: *font, 
+                font_size,
+                color: Color::from_rgba(255, 80, 80, 255),
+                ..Default::default()
+            }
+        );
+    }
+
+    // Black's clock by the top right corner of the board, White's by the bottom right
+    fn draw_clocks(&self, font: &Font, start: Vec2, board_width: f32, board_height: f32) {
+        let Some(clock) = &self.game.clock else {
+            return;
+        };
+
+        let font_size = ((self.size * 0.6) as u16).min((screen_width() / 30.) as u16);
+        // Clear of the coordinates on the right side
+        let x = start.x + board_width + self.size * if self.config.coordinates == Coordinates::AllSides { 1.8 } else { 0.8 };
+        for (color, y) in [(BoardCellOption::Black, start.y), (BoardCellOption::White, start.y + board_height - font_size as f32 * 1.2)] {
+            let (time, overtime) = clock.display(color);
+            let running = self.game.phase == Phase::Playing && self.game.board.to_move == color;
+            let text_color = if running || clock.player(color).flagged {
+                Color::from_rgba(255, 20, 40, 255)
+            } else {
+                self.theme.text_color()
+            };
+
+            self.theme.draw_stone(Vec2::new(x + font_size as f32 * 0.4, y - font_size as f32 * 0.3), font_size as f32 * 0.4, color, 1.0);
+            draw_text_ex(
+                time.as_str(),
+                x + font_size as f32,
+                y,
+                TextParams { 
+                    font: *font, 
+                    font_size,
+                    color: text_color,
+                    ..Default::default()
+                }
+            );
+            draw_text_ex(
+                overtime.as_str(),
+                x,
+                y + font_size as f32 * 1.2,
+                TextParams { 
+                    font: *font, 
+                    font_size: font_size * 3 / 4,
+                    color: self.theme.text_color(),
+                    ..Default::default()
+                }
+            );
+        }
+    }
+
+    fn update(& mut self) {
+        if screen_width() >= screen_height() {
+            self.size = screen_height() / (self.game.board.size + 4) as f32;
+        } else {
+            self.size = screen_width() / (self.game.board.size + 4) as f32;
+        }
+
+        let point = self.point_under_cursor();
+
+        self.error_timer = (self.error_timer - get_frame_time()).max(0.);
+        self.volume_timer = (self.volume_timer - get_frame_time()).max(0.);
+        if self.prompt.is_some() {
+            self.update_prompt();
+            return;
+        }
+        // Characters are queued until read, so typing outside a prompt must not show up in the next one
+        while get_char_pressed().is_some() {}
+
+        let editing = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+
+        self.update_audio();
+        if self.keys.pressed(Action::Theme) {
+            self.next_theme();
+        }
+        if self.keys.pressed(Action::Coordinates) {
+            self.config.coordinates = self.config.coordinates.next();
+            self.save_config();
+        }
+
+        if self.keys.pressed(Action::Review) {
+            self.toggle_review();
+        }
+
+        if self.review.is_some() {
+            self.update_review();
+        }
+        else if self.game.phase == Phase::Scoring {
+            if let (true, Some([x, y])) = (is_mouse_button_pressed(MouseButton::Left), point) {
+                self.game.toggle_dead(x, y);
+            }
+            if self.keys.pressed(Action::AgreeBlack) {
+                self.game.agree(BoardCellOption::Black);
+            }
+            if self.keys.pressed(Action::AgreeWhite) {
+                self.game.agree(BoardCellOption::White);
+            }
+            if self.keys.pressed(Action::ResumePlay) {
+                self.game.resume();
+                self.status = String::from("Play resumed");
+            }
+        }
+        else if let Some([x, y]) = point {
+            if is_mouse_button_pressed(MouseButton::Left) && !editing {
+                if self.is_human_turn() {
+                    let _ = self.play_move(Move::Play(x, y));
+                }
+            }
+            else if is_mouse_button_pressed(MouseButton::Left) {
+                self.edit(x, y, BoardCellOption::Black);
+            }
+            else if is_mouse_button_pressed(MouseButton::Right) && editing {
+                self.edit(x, y, BoardCellOption::White);
+            }
+            else if is_mouse_button_pressed(MouseButton::Middle) {
+                self.edit(x, y, BoardCellOption::None);
+            }
+        }
+
+        if self.keys.pressed(Action::Redo) {
+            self.redo();
+        }
+        else if self.keys.pressed(Action::Undo) {
+            self.undo();
+        }
+
+        if self.keys.pressed(Action::PreviousVariation) {
+            self.switch_variation(-1);
+        }
+        if self.keys.pressed(Action::NextVariation) {
+            self.switch_variation(1);
+        }
+        if self.keys.pressed(Action::DeleteBranch) && !self.engine.as_ref().is_some_and(|e| e.is_thinking()) && self.game.delete_branch() {
+            if let Some(engine) = &mut self.engine {
+                engine.sync(&self.game);
+            }
+            self.status = String::from("Branch deleted");
+        }
+
+        let can_move = self.review.is_none() && self.is_human_turn();
+        if self.keys.pressed(Action::Pass) && can_move {
+            let _ = self.play_move(Move::Pass);
+        }
+        if self.keys.pressed(Action::Resign) && can_move {
+            let _ = self.play_move(Move::Resign);
+        }
+
+        self.update_engine();
+
+        if self.keys.pressed(Action::SaveAs) {
+            let name = self.file.clone().unwrap_or_else(|| String::from("game.gs"));
+            self.prompt = Some(Prompt::SaveAs(name));
+        } else if self.keys.pressed(Action::Save) {
+            let path = self.file.clone().unwrap_or_else(|| String::from("save.gs"));
+            self.save(path.as_str());
+        }
+        if self.keys.pressed(Action::Open) {
+            self.prompt = Some(Prompt::Recent);
+        }
+        // Exports next to the current file
+        if self.keys.pressed(Action::Export) {
+            let path = match &self.file {
+                Some(file) => std::path::Path::new(file).with_extension("sgf").to_string_lossy().into_owned(),
+                None => String::from("save.sgf")
+            };
+            self.save(path.as_str());
+        }
+    }
+
+    fn play_move(&mut self, mv: Move) -> Result<(), IllegalMove> {
+        if let (true, Move::Play(x, y)) = (self.game.handicap_to_place > 0, mv) {
+            let result = self.game.place_handicap_stone(x, y);
+            self.play_effect(if result.is_ok() { Effect::Place } else { Effect::Illegal });
+            self.status = match &result {
+                Ok(_) if self.game.handicap_to_place > 0 => format!("Place {} more handicap stones", self.game.handicap_to_place),
+                Ok(_) => String::new(),
+                Err(e) => e.to_string()
+            };
+            if result.is_ok() && self.game.handicap_to_place == 0 {
+                if let Some(engine) = &mut self.engine {
+                    engine.sync(&self.game);
+                }
+            }
+            return result;
+        }
+
+        let color = self.game.board.to_move;
+        let result = self.game.play(mv);
+        match (&result, mv) {
+            (Err(_), _) => self.play_effect(Effect::Illegal),
+            (Ok(_), Move::Pass) => self.play_effect(Effect::Pass),
+            (Ok(_), Move::Resign) => {},
+            (Ok(outcome), _) if !outcome.captured.is_empty() || !outcome.self_captured.is_empty() => {
+                self.play_effect(Effect::Capture(outcome.captured.len() + outcome.self_captured.len()));
+            },
+            (Ok(_), _) => self.play_effect(Effect::Place)
+        }
+
+        if result.is_ok() {
+            if let Some(engine) = &mut self.engine {
+                if engine.color != color {
+                    engine.notify(color, mv, self.game.board.size);
+                }
+            }
+            self.autosave();
+        }
+
+        self.status = match &result {
+            Ok(outcome) if !outcome.self_captured.is_empty() => format!("Self-captured {} stones", outcome.self_captured.len()),
+            Ok(outcome) if !outcome.captured.is_empty() => format!("Captured {} stones", outcome.captured.len()),
+            Ok(_) if mv == Move::Pass => format!("{:?} passes", color),
+            Ok(_) => String::new(),
+            Err(e) => e.to_string()
+        };
+        result.map(|_| ())
+    }
+
+    // Against an engine, moves are taken back until it is the human's turn again
+    fn undo(&mut self) {
+        if self.engine.as_ref().is_some_and(|e| e.is_thinking()) {
+            return;
+        }
+
+        loop {
+            let node = self.game.current;
+            let edited = !self.game.tree.nodes[node].setup.is_empty();
+            match self.game.undo() {
+                Some((_, mv)) => {
+                    if let Some(engine) = &mut self.engine {
+                        if mv != Move::Resign {
+                            engine.undo();
+                        }
+                    }
+                    self.status = String::from("Move taken back");
+                    if self.is_human_turn() {
+                        break;
+                    }
+                },
+                None => {
+                    // GTP cannot take back an edit, so the engine gets the whole position again
+                    if edited && self.game.current != node {
+                        if let Some(engine) = &mut self.engine {
+                            engine.sync(&self.game);
+                        }
+                        self.status = String::from("Edit taken back");
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    // Adds, changes or removes a stone outside the normal turn order
+    fn edit(&mut self, x: usize, y: usize, piece: BoardCellOption) {
+        if self.engine.as_ref().is_some_and(|e| e.is_thinking()) {
+            return;
+        }
+
+        self.game.edit(x, y, piece);
+        if let Some(engine) = &mut self.engine {
+            engine.sync(&self.game);
+        }
+    }
+
+    fn toggle_review(&mut self) {
+        if self.engine.as_ref().is_some_and(|e| e.is_thinking()) {
+            return;
+        }
+
+        if self.review.take().is_some() {
+            if let Some(engine) = &mut self.engine {
+                engine.sync(&self.game);
+            }
+            self.status = String::new();
+        } else {
+            self.review = Some(Review::default());
+        }
+    }
+
+    // Arrow keys step through the record, Home/End jump, digits and Enter jump to a move, Space autoplays
+    fn update_review(&mut self) {
+        let Some(review) = &mut self.review else {
+            return;
+        };
+
+        if self.keys.pressed(Action::Back) {
+            self.game.undo();
+        }
+        if self.keys.pressed(Action::Forward) {
+            self.game.redo();
+        }
+        if self.keys.pressed(Action::First) {
+            self.game.goto(0);
+        }
+        if self.keys.pressed(Action::Last) {
+            self.game.goto(self.game.tree.line_end(self.game.current));
+        }
+
+        for (i, key) in DIGIT_KEYS.iter().enumerate() {
+            if is_key_pressed(*key) {
+                review.jump += i.to_string().as_str();
+            }
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            if let Ok(n) = review.jump.parse::<usize>() {
+                self.game.goto_move(n);
+            }
+            review.jump.clear();
+        }
+
+        if self.keys.pressed(Action::Autoplay) {
+            review.autoplay = !review.autoplay;
+            review.timer = 0.0;
+        }
+        if self.keys.pressed(Action::Faster) {
+            review.speed = (review.speed * 0.5).max(0.125);
+        }
+        if self.keys.pressed(Action::Slower) {
+            review.speed = (review.speed * 2.0).min(8.0);
+        }
+
+        if review.autoplay {
+            review.timer += get_frame_time();
+            if review.timer >= review.speed {
+                review.timer = 0.0;
+                if self.game.tree.selected_child(self.game.current).is_none() {
+                    review.autoplay = false;
+                } else {
+                    self.game.redo();
+                }
+            }
+        }
+    }
+
+    fn switch_variation(&mut self, offset: isize) {
+        if self.engine.as_ref().is_some_and(|e| e.is_thinking()) {
+            return;
+        }
+
+        if self.game.switch_variation(offset) {
+            if let Some(engine) = &mut self.engine {
+                engine.sync(&self.game);
+            }
+            let (index, count) = self.game.tree.variations(self.game.current);
+            self.status = format!("Variation {}/{}", index + 1, count);
+        }
+    }
+
+    fn redo(&mut self) {
+        if self.engine.as_ref().is_some_and(|e| e.is_thinking()) {
+            return;
+        }
+
+        let node = self.game.current;
+        match self.game.redo() {
+            Some((color, mv)) => {
+                if let Some(engine) = &mut self.engine {
+                    engine.notify(color, mv, self.game.board.size);
+                }
+                self.status = String::from("Move replayed");
+            },
+            // Setup nodes are replayed by rebuilding the position, which the engine needs in full
+            None if self.game.current != node => {
+                if let Some(engine) = &mut self.engine {
+                    engine.sync(&self.game);
+                }
+            },
+            None => {}
+        }
+    }
+
+    // Asks the engine for a move on its turn and applies its answer through the normal rules
+    fn update_engine(&mut self) {
+        let size = self.game.board.size;
+        let Some(engine) = &mut self.engine else {
+            return;
+        };
+        if self.game.phase != Phase::Playing || self.review.is_some() {
+            return;
+        }
+        if engine.color == self.game.board.to_move {
+            engine.request_move();
+        }
+
+        match engine.poll(size) {
+            None => {},
+            Some(Ok(mv)) => {
+                if let Err(e) = self.play_move(mv) {
+                    self.status = EngineError::IllegalMove(format_move(mv, size), e).to_string();
+                    self.engine = None;
+                }
+            },
+            Some(Err(e)) => {
+                self.status = e.to_string();
+                self.engine = None;
+            }
+        }
+    }
+
+    fn draw_banner(&self, font: &Font) {
+        let text = match (self.game.phase, &self.game.result) {
+            (Phase::Finished, Some(result)) => result.to_string(),
+            _ => return
+        };
+
+        let font_size = ((self.size * 1.2) as u16).min((screen_width() / 20.) as u16);
+        let dimensions = measure_text(text.as_str(), Some(*font), font_size, 1.0);
+
+        draw_rectangle(
+            0.,
+            screen_height() * 0.5 - dimensions.height * 1.5,
+            screen_width(),
+            dimensions.height * if self.game.score.is_some() { 8. } else { 3. },
+            Color::from_rgba(0, 0, 0, 180)
+        );
+        draw_text_ex(
+            text.as_str(),
+            screen_width() * 0.5 - dimensions.width * 0.5,
+            screen_height() * 0.5 + dimensions.offset_y * 0.5,
+            TextParams { 
+                font: *font, 
+                font_size,
+                color: WHITE,
+                ..Default::default()
+            }
+        );
+
+        if let Some(score) = &self.game.score {
+            let font_size = font_size / 2;
+            for (i, color) in [BoardCellOption::Black, BoardCellOption::White].iter().enumerate() {
+                let line = score.breakdown(*color);
+                let dimensions = measure_text(line.as_str(), Some(*font), font_size, 1.0);
+                draw_text_ex(
+                    line.as_str(),
+                    screen_width() * 0.5 - dimensions.width * 0.5,
+                    screen_height() * 0.5 + dimensions.height * (4.0 + 1.5 * i as f32),
+                    TextParams { 
+                        font: *font, 
+                        font_size,
+                        color: WHITE,
+                        ..Default::default()
+                    }
+                );
+            }
+        }
+    }
+}
+
+pub fn window_conf() -> Conf {
+    // Problems with the config file are shown once the window is open
+    let config = Config::load().unwrap_or_default();
+    Conf { 
+        window_title: String::from("Go"), 
+        window_width: config.window_width, 
+        window_height: config.window_height,
+        sample_count: 16,
+        ..Default::default()
+    }
+}
+
+pub async fn run(options: Options) {
+    let mut errors = vec![];
+
+    let mut config_failed = false;
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            errors.push(e);
+            config_failed = true;
+            Config::default()
+        }
+    };
+    // The game works without music, and with macroquad's built-in font
+    let mut music = Music::default();
+    if options.music && config.music {
+        music = Music::new(config.music_dir.as_str());
+        let volume = if config.muted { 0. } else { config.music_volume };
+        music.next(config.shuffle, volume);
+    }
+
+    let font = match load_ttf_font("font_regular.ttf").await {
+        Ok(font) => font,
+        Err(e) => {
+            errors.push(FileError::Asset { path: String::from("font_regular.ttf"), message: e.to_string() });
+            Font::default()
+        }
+    };
+
+    let mut go_game = GoBoardUi::new(config);
+    go_game.music = music;
+    // Changes are only stored once the file that failed to load is kept somewhere safe
+    if config_failed {
+        match Config::back_up() {
+            Ok(path) => go_game.status = format!("Using default settings, the old file is kept as {}", path.display()),
+            Err(e) => {
+                errors.push(e);
+                go_game.config_writable = false;
+                go_game.status = String::from("Using default settings, changes will not be saved");
+            }
+        }
+    }
+    if let Some(path) = options.load.as_deref() {
+        go_game.open(path);
+    } else if options.size.is_some() || options.rules.is_some() || options.komi.is_some() {
+        // Options given on the command line win over the config for this game only
+        let rules = options.rules.unwrap_or(go_game.config.ruleset);
+        let mut game = Game::new(GoBoard::new(options.size.unwrap_or(go_game.config.size), rules));
+        game.komi = options.komi.or(go_game.config.komi).unwrap_or_else(|| rules.default_komi());
+        go_game.game = game;
+    }
+    if go_game.config.sounds {
+        go_game.effects = Effects::load(&mut errors).await;
+    }
+    // Only for this session; switching themes in the game stores the choice
+    if let Some(name) = options.theme.as_deref() {
+        go_game.set_theme(name);
+    }
+    for e in &errors {
+        go_game.show_error(e);
+    }
+
+    // A lock file left behind by the last session means it crashed
+    let crashed = session::crashed_session();
+    let autosave = session::autosave_path(SystemTime::now());
+    match session::prepare_autosaves().and_then(|_| session::begin_session(autosave.as_str())) {
+        Ok(_) => go_game.autosave = Some(autosave),
+        Err(e) => go_game.show_error(&e)
+    }
+    if let Some(path) = crashed {
+        go_game.prompt = Some(Prompt::Resume(path));
+    }
+    prevent_quit();
+
+    if let Some(stones) = options.handicap {
+        let size = go_game.game.board.size;
+        match handicap::fixed_handicap(size, stones) {
+            Some(points) if go_game.game.set_handicap(&points) => {},
+            _ => go_game.status = format!("A {}x{} game cannot take {} fixed handicap stones", size, size, stones)
+        }
+    }
+    if let Some(stones) = options.free_handicap {
+        if go_game.game.start_free_handicap(stones) {
+            go_game.status = format!("Place {} handicap stones", stones);
+        } else {
+            go_game.status = format!("Cannot place {} free handicap stones", stones);
+        }
+    }
+
+    if let Some(control) = options.time {
+        go_game.game.clock = Some(GameClock::new(control));
+    }
+
+    if options.review {
+        go_game.game.goto(0);
+        go_game.review = Some(Review::default());
+    }
+
+    if let Some((color, command)) = options.engine() {
+        match EnginePlayer::spawn(command, color, &go_game.game) {
+            Ok(engine) => go_game.engine = Some(engine),
+            Err(e) => go_game.status = e.to_string()
+        }
+    }
+
+    // Set after a frame that loaded a track, so the clock is not charged for the load
+    let mut skip_clock = false;
+    loop {
+        let delta = if std::mem::take(&mut skip_clock) { 0. } else { get_frame_time() };
+
+        go_game.update();
+        if go_game.review.is_none() && go_game.game.tick_clock(delta) {
+            go_game.status = String::from("Time is up");
+            go_game.play_effect(Effect::Timeout);
+        }
+
+        let volume = if go_game.config.muted { 0. } else { go_game.config.music_volume };
+        // Tracks are not looped, so the playlist moves on by itself
+        let started = (std::mem::take(&mut go_game.next_track) | go_game.music.track_ended(delta))
+            && go_game.music.next(go_game.config.shuffle, volume);
+        let loaded = match go_game.music.poll(volume).await {
+            Some(Ok(())) => true,
+            Some(Err(e)) => {
+                go_game.show_error(&e);
+                false
+            },
+            None => false
+        };
+        if started || loaded {
+            if let Some((name, _)) = go_game.music.playing() {
+                go_game.status = format!("Playing {}", name);
+            }
+        }
+        skip_clock = loaded;
+        go_game.music.set_volume(volume);
+
+        go_game.draw(&font);
+
+        if is_quit_requested() {
+            if (screen_width() as i32, screen_height() as i32) != (go_game.config.window_width, go_game.config.window_height) {
+                go_game.config.window_width = screen_width() as i32;
+                go_game.config.window_height = screen_height() as i32;
+                go_game.save_config();
+            }
+            session::end_session();
+            break;
+        }
+
+        next_frame().await
+    }
+}
\ No newline at end of file